@@ -0,0 +1,80 @@
+//! Bundles the error index of the rustc building cargo-explain into it, so
+//! it can explain codes on machines without a rustc of their own.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// Kept in step with `MAX_ERROR_CODE` in src/index.rs.
+const MAX_ERROR_CODE: usize = 999;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=RUSTC");
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let out = PathBuf::from(env::var_os("OUT_DIR").unwrap()).join("bundled-index.bin");
+
+    let version = Command::new(&rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+    let version = match version {
+        Some(version) => version,
+        None => {
+            println!("cargo:warning=couldn't run rustc, so no error index is bundled");
+            fs::write(&out, miniz_oxide::deflate::compress_to_vec(b"", 9)).unwrap();
+            return;
+        }
+    };
+
+    // Each entry is the code and its explanation, empty for a code without
+    // one, as rustc prints them
+    let next = AtomicUsize::new(1);
+    let entries = Mutex::new(Vec::new());
+    let workers = thread::available_parallelism().map_or(4, |n| n.get());
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let n = next.fetch_add(1, Ordering::Relaxed);
+                if n > MAX_ERROR_CODE {
+                    break;
+                }
+                let code = format!("E{:04}", n);
+                let output = match Command::new(&rustc).args(["--explain", &code]).output() {
+                    Ok(output) => output,
+                    Err(_) => continue,
+                };
+                let explanation = if output.status.success() {
+                    String::from_utf8_lossy(&output.stdout).into_owned()
+                } else if String::from_utf8_lossy(&output.stderr)
+                    .contains("no extended information")
+                {
+                    String::new()
+                } else {
+                    continue;
+                };
+                entries.lock().unwrap().push((code, explanation));
+            });
+        }
+    });
+    let mut entries = entries.into_inner().unwrap();
+    entries.sort();
+
+    // The version, then entries, fields split by a unit separator and
+    // records by a record separator
+    let mut bundle = version;
+    for (code, explanation) in entries {
+        bundle.push_str(&format!("\x1e{}\x1f{}", code, explanation));
+    }
+    fs::write(
+        &out,
+        miniz_oxide::deflate::compress_to_vec(bundle.as_bytes(), 9),
+    )
+    .unwrap();
+}
@@ -0,0 +1,255 @@
+//! The blocks and spans an explanation is parsed into, built from
+//! pulldown-cmark's event stream.
+
+use pulldown_cmark::{Alignment, CodeBlockKind, Event, Options, Parser, Tag};
+
+use crate::table::{Align, Table};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Block {
+    Header(Vec<Span>, usize),
+    Paragraph(Vec<Span>),
+    Blockquote(Vec<Block>),
+    /// A code block's info string, if it had one, and its code.
+    CodeBlock(Option<String>, String),
+    List(List),
+    Table(Table),
+    /// Text that's printed as it is, like already rendered output.
+    Raw(String),
+    Hr,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct List {
+    /// The first item's number, for ordered lists.
+    pub start: Option<usize>,
+    /// Whether items are separated by blank lines.
+    pub loose: bool,
+    pub items: Vec<ListItem>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ListItem {
+    /// An item that's nothing but a line or so of text.
+    Simple(Vec<Span>),
+    Paragraph(Vec<Block>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Span {
+    /// A hard line break.
+    Break,
+    Text(String),
+    Code(String),
+    /// A link's text, URL, and title.
+    Link(Vec<Span>, String, Option<String>),
+    /// An image's alt text, URL, and title.
+    Image(String, String, Option<String>),
+    Emphasis(Vec<Span>),
+    Strong(Vec<Span>),
+}
+
+type Events<'a> = Parser<'a, 'a>;
+
+/// Parses CommonMark, with pipe tables.
+pub fn parse(input: &str) -> Vec<Block> {
+    let mut events = Parser::new_ext(input, Options::ENABLE_TABLES);
+    blocks(&mut events, &mut false)
+}
+
+/// Parses blocks up to the end of the enclosing container. Text outside of
+/// any paragraph, as in tight list items, is gathered into paragraphs of its
+/// own; `explicit` is set if any real paragraphs turn up.
+fn blocks(events: &mut Events, explicit: &mut bool) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut loose_spans = Vec::new();
+    let mut html = String::new();
+    while let Some(event) = events.next() {
+        if !matches!(event, Event::Html(_)) && !html.is_empty() {
+            blocks.extend(html_block(&std::mem::take(&mut html)));
+        }
+        let block = match event {
+            Event::End(_) => break,
+            Event::Start(Tag::Paragraph) => {
+                *explicit = true;
+                Block::Paragraph(spans(events))
+            }
+            Event::Start(Tag::Heading(level, ..)) => Block::Header(spans(events), level as usize),
+            Event::Start(Tag::BlockQuote) => Block::Blockquote(self::blocks(events, &mut false)),
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let info = match kind {
+                    CodeBlockKind::Fenced(info) if !info.trim().is_empty() => {
+                        Some(info.to_string())
+                    }
+                    _ => None,
+                };
+                let code = text(events);
+                Block::CodeBlock(info, code.trim_end_matches('\n').to_string())
+            }
+            Event::Start(Tag::List(start)) => Block::List(list(events, start)),
+            Event::Start(Tag::Table(align)) => Block::Table(table(events, &align)),
+            Event::Rule => Block::Hr,
+            Event::Html(text) => {
+                html.push_str(&text);
+                continue;
+            }
+            event => {
+                loose_spans.extend(span(event, events));
+                continue;
+            }
+        };
+        if !loose_spans.is_empty() {
+            blocks.push(Block::Paragraph(merge_text(std::mem::take(
+                &mut loose_spans,
+            ))));
+        }
+        blocks.push(block);
+    }
+    if !html.is_empty() {
+        blocks.extend(html_block(&html));
+    }
+    if !loose_spans.is_empty() {
+        blocks.push(Block::Paragraph(merge_text(loose_spans)));
+    }
+    blocks
+}
+
+/// HTML is shown as the source it is, except for comments, which are left
+/// out.
+fn html_block(html: &str) -> Option<Block> {
+    let html = html.trim_end();
+    if html.trim_start().starts_with("<!--") && html.ends_with("-->") {
+        None
+    } else {
+        Some(Block::Raw(html.to_string()))
+    }
+}
+
+fn list(events: &mut Events, start: Option<u64>) -> List {
+    let mut items = Vec::new();
+    let mut loose = false;
+    while let Some(Event::Start(Tag::Item)) = events.next() {
+        let mut explicit = false;
+        let mut blocks = blocks(events, &mut explicit);
+        loose |= explicit;
+        let item = match blocks.as_slice() {
+            [] => ListItem::Simple(Vec::new()),
+            [Block::Paragraph(_)] if !explicit => match blocks.pop() {
+                Some(Block::Paragraph(spans)) => ListItem::Simple(spans),
+                _ => unreachable!(),
+            },
+            _ => ListItem::Paragraph(blocks),
+        };
+        items.push(item);
+    }
+    List {
+        start: start.map(|start| start as usize),
+        loose,
+        items,
+    }
+}
+
+fn table(events: &mut Events, align: &[Alignment]) -> Table {
+    let align = align
+        .iter()
+        .map(|align| match align {
+            Alignment::Center => Align::Center,
+            Alignment::Right => Align::Right,
+            Alignment::Left | Alignment::None => Align::Left,
+        })
+        .collect();
+    let mut header = Vec::new();
+    let mut rows = Vec::new();
+    while let Some(event) = events.next() {
+        match event {
+            Event::Start(Tag::TableHead) => header = row(events),
+            Event::Start(Tag::TableRow) => rows.push(row(events)),
+            _ => break,
+        }
+    }
+    Table {
+        header,
+        align,
+        rows,
+    }
+}
+
+fn row(events: &mut Events) -> Vec<Vec<Span>> {
+    let mut cells = Vec::new();
+    while let Some(Event::Start(Tag::TableCell)) = events.next() {
+        cells.push(spans(events));
+    }
+    cells
+}
+
+/// Parses spans up to the end of the enclosing block or span.
+fn spans(events: &mut Events) -> Vec<Span> {
+    let mut spans = Vec::new();
+    while let Some(event) = events.next() {
+        if let Event::End(_) = event {
+            break;
+        }
+        spans.extend(span(event, events));
+    }
+    merge_text(spans)
+}
+
+/// All the text up to the end of the enclosing block, as in a code block.
+fn text(events: &mut Events) -> String {
+    let mut text = String::new();
+    for event in events {
+        match event {
+            Event::Text(t) => text.push_str(&t),
+            Event::End(_) => break,
+            _ => {}
+        }
+    }
+    text
+}
+
+fn span(event: Event, events: &mut Events) -> Option<Span> {
+    let span = match event {
+        Event::Html(html) if is_line_break(&html) => Span::Break,
+        Event::Text(text) | Event::Html(text) => Span::Text(text.to_string()),
+        Event::Code(code) => Span::Code(code.to_string()),
+        Event::SoftBreak => Span::Text(" ".to_string()),
+        Event::HardBreak => Span::Break,
+        Event::Start(Tag::Emphasis) => Span::Emphasis(spans(events)),
+        Event::Start(Tag::Strong) => Span::Strong(spans(events)),
+        Event::Start(Tag::Link(_, url, title)) => {
+            Span::Link(spans(events), url.to_string(), non_empty(&title))
+        }
+        Event::Start(Tag::Image(_, url, title)) => {
+            let alt = crate::plain_text(&spans(events));
+            Span::Image(alt, url.to_string(), non_empty(&title))
+        }
+        Event::Start(_) => Span::Text(crate::plain_text(&spans(events))),
+        _ => return None,
+    };
+    Some(span)
+}
+
+/// Whether inline HTML is a `<br>` tag, the only way to break a line in a
+/// table cell.
+fn is_line_break(html: &str) -> bool {
+    let tag = html.to_ascii_lowercase();
+    let tag = tag.trim_start_matches('<').trim_end_matches('>');
+    matches!(tag.trim_end_matches('/').trim(), "br")
+}
+
+fn non_empty(text: &str) -> Option<String> {
+    Some(text.to_string()).filter(|text| !text.is_empty())
+}
+
+/// The parser splits text up around escapes and the like; joins it back
+/// together.
+fn merge_text(spans: Vec<Span>) -> Vec<Span> {
+    let mut merged: Vec<Span> = Vec::with_capacity(spans.len());
+    for span in spans {
+        match (merged.last_mut(), span) {
+            (Some(Span::Text(previous)), Span::Text(text)) => previous.push_str(&text),
+            (_, span) => merged.push(span),
+        }
+    }
+    merged
+}
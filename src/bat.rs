@@ -0,0 +1,30 @@
+//! Highlighting code blocks with `bat`, so they pick up the theme and other
+//! settings from the user's bat config.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// Highlights `code` as `language` by piping it through `bat`. Returns
+/// `None` if bat isn't on the `PATH` or fails, so the caller can fall back
+/// to syntect.
+pub fn highlight(code: &str, language: &str) -> Option<String> {
+    let mut child = Command::new("bat")
+        .args(["--color=always", "--paging=never", "--language", language])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    // Written from another thread, so a block bigger than the pipes can
+    // hold doesn't leave bat waiting on its stdout while we wait on its stdin
+    let mut stdin = child.stdin.take()?;
+    let code = code.to_string();
+    let writer = thread::spawn(move || stdin.write_all(code.as_bytes()));
+    let output = child.wait_with_output().ok()?;
+    writer.join().ok()?.ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
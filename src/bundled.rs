@@ -0,0 +1,56 @@
+//! The error index bundled into cargo-explain when it was built, for
+//! explaining codes where there's no rustc to ask.
+
+use std::sync::OnceLock;
+
+use crate::index::{self, IndexEntry};
+
+static BUNDLE: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/bundled-index.bin"));
+
+struct Bundle {
+    /// The `rustc --version` of the rustc the index came from.
+    version: String,
+    /// Codes and their explanations, empty for codes without one.
+    entries: Vec<(String, String)>,
+}
+
+fn bundle() -> &'static Bundle {
+    static UNPACKED: OnceLock<Bundle> = OnceLock::new();
+    UNPACKED.get_or_init(|| {
+        let bytes = miniz_oxide::inflate::decompress_to_vec(BUNDLE).unwrap_or_default();
+        let text = String::from_utf8_lossy(&bytes);
+        let mut records = text.split('\x1e');
+        let version = records.next().unwrap_or_default().to_string();
+        let entries = records
+            .filter_map(|record| record.split_once('\x1f'))
+            .map(|(code, explanation)| (code.to_string(), explanation.to_string()))
+            .collect();
+        Bundle { version, entries }
+    })
+}
+
+/// The rustc the bundled index came from, like `rustc 1.80.0 (...)`, if
+/// one was bundled at all.
+pub fn version() -> Option<&'static str> {
+    Some(bundle().version.as_str()).filter(|version| !version.is_empty())
+}
+
+/// The last code in the bundled index, if one was bundled at all.
+pub fn last_code() -> Option<&'static str> {
+    bundle().entries.last().map(|(code, _)| code.as_str())
+}
+
+/// A code's entry in the bundled index, as `index::probe` would find it.
+pub fn probe(code: &str) -> Option<IndexEntry> {
+    let entries = &bundle().entries;
+    let at = entries
+        .binary_search_by(|(c, _)| c.as_str().cmp(code))
+        .ok()?;
+    let explanation = &entries[at].1;
+    Some(IndexEntry {
+        code: code.to_string(),
+        title: index::title_of(explanation),
+        has_explanation: !explanation.is_empty(),
+        explanation: explanation.clone(),
+    })
+}
@@ -0,0 +1,343 @@
+//! The command line, as clap parses it. Settings are resolved from it into
+//! `Options` by the `settings` module. Flags that change how explanations
+//! look can also be set by a `CARGO_EXPLAIN_*` variable.
+
+use cargo_explain::{InlineCode, Spacing};
+use clap::{Parser, Subcommand};
+use clap_complete::Shell;
+use std::path::PathBuf;
+
+use crate::{ColorWhen, Format, GroupBy, Hyperlinks, Profile, TrailingNewline};
+
+/// A syntax highlighting replacement for `rustc --explain`.
+#[derive(Parser)]
+#[command(about, max_term_width = 100, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// The error codes to explain, like E0308, or ranges like E0300..E0320,
+    /// or lints like unused_must_use, rustdoc::bare_urls or
+    /// clippy::needless_return.
+    #[arg(value_name = "CODE")]
+    pub codes: Vec<String>,
+
+    /// An error code to explain, as `rustc --explain` takes it, before any
+    /// other CODEs.
+    #[arg(long, value_name = "CODE")]
+    pub explain: Option<String>,
+
+    /// Explain the code whose explanation best matches a compiler message.
+    #[arg(long = "match", value_name = "MESSAGE")]
+    pub match_message: Option<String>,
+
+    /// Explain every error code in compiler output, given here or read from
+    /// stdin.
+    #[arg(long, value_name = "MESSAGE", num_args = 0..=1)]
+    pub from_message: Option<Option<String>>,
+
+    /// Explain every error in a build log, like one from CI, read from FILE
+    /// or stdin.
+    #[arg(long, value_name = "FILE", num_args = 0..=1)]
+    pub log: Option<Option<PathBuf>>,
+
+    /// Explain the lints in cargo's report of dependencies that a future
+    /// Rust will reject, by dependency.
+    #[arg(long)]
+    pub future_incompat: bool,
+
+    /// Explain every error code mentioned in the comments of a Rust file.
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["codes", "explain"])]
+    pub explain_all_in: Option<PathBuf>,
+
+    /// Explain ranges of over 100 codes.
+    #[arg(long)]
+    pub force: bool,
+
+    /// Prompt for codes when none are given, even off a terminal.
+    #[arg(long, overrides_with = "no_prompt")]
+    pub prompt: bool,
+
+    /// Never prompt for codes.
+    #[arg(long, overrides_with = "prompt")]
+    pub no_prompt: bool,
+
+    /// Print a JSON index of every error code and its title.
+    #[arg(long)]
+    pub index: bool,
+
+    /// Read codes from stdin one per line, answering each with its rendered
+    /// explanation and a NUL byte.
+    #[arg(long)]
+    pub serve: bool,
+
+    /// Read cargo's JSON diagnostics from stdin, printing each followed by
+    /// the explanation of its code the first time it comes up.
+    #[arg(long)]
+    pub pipe: bool,
+
+    /// Check the project whenever its files change, explaining each error
+    /// code the first time it comes up.
+    #[arg(long)]
+    pub watch: bool,
+
+    /// Run a rustc command line, as RUSTC_WRAPPER, explaining each error
+    /// code when it fails.
+    #[arg(long, value_name = "RUSTC", num_args = 0.., allow_hyphen_values = true)]
+    pub wrapper: Option<Vec<String>>,
+
+    /// Apply a preset group of options, which other flags still override.
+    #[arg(long, env = "CARGO_EXPLAIN_PROFILE", value_enum, value_name = "NAME")]
+    pub profile: Option<Profile>,
+
+    /// The syntect theme to highlight code with.
+    #[arg(long, env = "CARGO_EXPLAIN_THEME", value_name = "NAME")]
+    pub theme: Option<String>,
+
+    /// Show code under two themes, marking the tokens they color differently.
+    #[arg(long, num_args = 2, value_names = ["A", "B"])]
+    pub diff_themes: Option<Vec<String>>,
+
+    /// When to render styled output; `always` keeps it when piped.
+    #[arg(long, env = "CARGO_EXPLAIN_COLOR", value_enum, value_name = "WHEN")]
+    pub color: Option<ColorWhen>,
+
+    /// Render without color, by bold, italic, and underline alone.
+    #[arg(long, env = "CARGO_EXPLAIN_MONO")]
+    pub mono: bool,
+
+    /// Render in color, even where a profile or the environment asks for
+    /// --mono.
+    #[arg(long)]
+    pub no_mono: bool,
+
+    /// Draw bullets, rules, borders, and badges in plain ASCII.
+    #[arg(long, env = "CARGO_EXPLAIN_ASCII")]
+    pub ascii: bool,
+
+    /// The background behind code, as #rrggbb or `none`.
+    #[arg(long, env = "CARGO_EXPLAIN_CODE_BG", value_name = "COLOR")]
+    pub code_bg: Option<String>,
+
+    /// How code within prose is drawn.
+    #[arg(long, env = "CARGO_EXPLAIN_INLINE_CODE", value_name = "STYLE", value_parser = parse_inline_code)]
+    pub inline_code: Option<InlineCode>,
+
+    /// Wrap prose at this many columns instead of the terminal's width.
+    #[arg(long, env = "CARGO_EXPLAIN_WIDTH", value_name = "COLUMNS")]
+    pub width: Option<usize>,
+
+    /// Columns each level of quote or list nesting is indented.
+    #[arg(long, env = "CARGO_EXPLAIN_INDENT", value_name = "COLUMNS")]
+    pub indent: Option<usize>,
+
+    /// Blank lines between blocks, around code, and before headers, like
+    /// blocks=1,code=0,headers=2.
+    #[arg(long, env = "CARGO_EXPLAIN_SPACING", value_name = "LIST", value_parser = parse_spacing)]
+    pub spacing: Option<Spacing>,
+
+    /// Hyphenate long words in prose.
+    #[arg(long, env = "CARGO_EXPLAIN_HYPHENATE")]
+    pub hyphenate: bool,
+
+    /// Join adjacent code blocks of the same language.
+    #[arg(long, env = "CARGO_EXPLAIN_MERGE_CODE")]
+    pub merge_code: bool,
+
+    /// Keep adjacent code blocks apart, even where a profile merges them.
+    #[arg(long)]
+    pub no_merge_code: bool,
+
+    /// Pad code lines so each block is a clean rectangle.
+    #[arg(long, env = "CARGO_EXPLAIN_PAD_CODE")]
+    pub pad_code: bool,
+
+    /// Leave code lines unpadded, even where a profile pads them.
+    #[arg(long)]
+    pub no_pad_code: bool,
+
+    /// Number the lines of code blocks.
+    #[arg(long, env = "CARGO_EXPLAIN_LINE_NUMBERS")]
+    pub line_numbers: bool,
+
+    /// Draw a frame around each code block, with its language on top.
+    #[arg(long, env = "CARGO_EXPLAIN_FRAME_CODE")]
+    pub frame_code: bool,
+
+    /// Tint the lines that change between an example and its fix.
+    #[arg(long, env = "CARGO_EXPLAIN_DIFF_EXAMPLES")]
+    pub diff_examples: bool,
+
+    /// Show the lines rustdoc hides from examples, dimmed.
+    #[arg(long, env = "CARGO_EXPLAIN_SHOW_HIDDEN")]
+    pub show_hidden: bool,
+
+    /// Highlight code with bat, using your bat config.
+    #[cfg(feature = "bat")]
+    #[arg(long, env = "CARGO_EXPLAIN_USE_BAT")]
+    pub use_bat: bool,
+
+    /// Start explanations with at least three headers with a table of
+    /// contents.
+    #[arg(long, env = "CARGO_EXPLAIN_TOC")]
+    pub toc: bool,
+
+    /// Never add a table of contents, even to long explanations.
+    #[arg(long, env = "CARGO_EXPLAIN_NO_TOC")]
+    pub no_toc: bool,
+
+    /// Number headers by section.
+    #[arg(long, env = "CARGO_EXPLAIN_NUMBERED")]
+    pub numbered: bool,
+
+    /// Draw a rule under first and second level headers.
+    #[arg(long, env = "CARGO_EXPLAIN_HEADER_RULES")]
+    pub header_rules: bool,
+
+    /// Leave off the list of related error codes.
+    #[arg(long, env = "CARGO_EXPLAIN_NO_RELATED")]
+    pub no_related: bool,
+
+    /// Set prose beside its code examples on wide terminals.
+    #[arg(long, env = "CARGO_EXPLAIN_COLUMNS")]
+    pub columns: bool,
+
+    /// Keep prose and code in one column, even where a profile sets them
+    /// side by side.
+    #[arg(long)]
+    pub no_columns: bool,
+
+    /// Cut long explanations off at a section boundary within this many
+    /// blocks.
+    #[arg(long, env = "CARGO_EXPLAIN_MAX_BLOCKS", value_name = "N")]
+    pub max_blocks: Option<usize>,
+
+    /// Show all of an explanation, despite --max-blocks.
+    #[arg(long)]
+    pub full: bool,
+
+    /// Section the output when explaining several codes.
+    #[arg(
+        long,
+        env = "CARGO_EXPLAIN_GROUP_BY",
+        value_enum,
+        value_name = "GROUPING"
+    )]
+    pub group_by: Option<GroupBy>,
+
+    /// When links are clickable OSC 8 hyperlinks.
+    #[arg(
+        long,
+        env = "CARGO_EXPLAIN_HYPERLINKS",
+        value_enum,
+        value_name = "WHEN"
+    )]
+    pub hyperlinks: Option<Hyperlinks>,
+
+    /// Keep the code and title pinned at the top of the pager, when it's
+    /// less 600 or newer.
+    #[arg(long, env = "CARGO_EXPLAIN_PIN_TITLE")]
+    pub pin_title: bool,
+
+    /// Print explanations as structured JSON.
+    #[arg(long, env = "CARGO_EXPLAIN_FORMAT", value_enum, value_name = "FORMAT")]
+    pub format: Option<Format>,
+
+    /// Print JSON with each block's rendered terminal output.
+    #[arg(long)]
+    pub json_render: bool,
+
+    /// Ask rustc for explanations translated into this locale.
+    #[arg(long, env = "CARGO_EXPLAIN_LANG_LOCALE", value_name = "LOCALE")]
+    pub lang_locale: Option<String>,
+
+    /// Page output through this command instead of $PAGER.
+    #[arg(long, env = "CARGO_EXPLAIN_PAGER", value_name = "COMMAND")]
+    pub pager: Option<String>,
+
+    /// Don't page output.
+    #[arg(long, env = "CARGO_EXPLAIN_NO_PAGER")]
+    pub no_pager: bool,
+
+    /// Whether output ends with a newline.
+    #[arg(
+        long,
+        env = "CARGO_EXPLAIN_TRAILING_NEWLINE",
+        value_enum,
+        value_name = "WHEN"
+    )]
+    pub trailing_newline: Option<TrailingNewline>,
+
+    /// Leave off the final style reset.
+    #[arg(long, env = "CARGO_EXPLAIN_NO_TRAILING_RESET")]
+    pub no_trailing_reset: bool,
+
+    /// Copy the online documentation URL to the clipboard.
+    #[arg(long)]
+    pub copy_url: bool,
+
+    /// Open the online documentation in a browser instead.
+    #[arg(long)]
+    pub open: bool,
+
+    /// The base URL of the online documentation.
+    #[arg(long, env = "CARGO_EXPLAIN_DOCS_BASE", value_name = "URL")]
+    pub docs_base: Option<String>,
+
+    /// Ask this rustup toolchain, like `nightly` or `1.70`, instead of the
+    /// default rustc. `+nightly` before everything else does the same.
+    #[arg(long, value_name = "NAME")]
+    pub toolchain: Option<String>,
+
+    /// Show how an explanation's text differs between two rustup
+    /// toolchains, like `stable nightly`.
+    #[arg(long, num_args = 2, value_names = ["A", "B"], conflicts_with_all = ["toolchain", "rustc"])]
+    pub diff: Option<Vec<String>>,
+
+    /// Ask this rustc binary, like a local compiler build, instead of the
+    /// one on the PATH.
+    #[arg(
+        long,
+        env = "CARGO_EXPLAIN_RUSTC",
+        value_name = "PATH",
+        conflicts_with = "toolchain"
+    )]
+    pub rustc: Option<PathBuf>,
+
+    /// Always ask rustc, never the error index bundled for machines
+    /// without one.
+    #[arg(long, conflicts_with_all = ["toolchain", "rustc"])]
+    pub live: bool,
+
+    /// Explain codes as an earlier Rust release, like 1.70.0, documented
+    /// them, downloading its error index the first time.
+    #[arg(long, value_name = "VERSION", conflicts_with_all = ["toolchain", "rustc", "live", "diff"])]
+    pub release: Option<String>,
+
+    /// Print cargo-explain's version and the rustc toolchain explanations
+    /// come from.
+    #[arg(short = 'V', long)]
+    pub version: bool,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Print a completion script for a shell, error codes included.
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+fn parse_inline_code(value: &str) -> Result<InlineCode, String> {
+    match value {
+        "tint" => Ok(InlineCode::Tint),
+        "highlight" => Ok(InlineCode::Highlight),
+        _ => Err("expected tint or highlight".to_string()),
+    }
+}
+
+fn parse_spacing(value: &str) -> Result<Spacing, String> {
+    Spacing::parse(value)
+        .ok_or_else(|| "expected a list like blocks=1,code=1,headers=1".to_string())
+}
@@ -0,0 +1,27 @@
+//! Shell completion scripts, generated from the clap command.
+
+use clap::builder::{PossibleValue, PossibleValuesParser};
+use clap::CommandFactory;
+use clap_complete::Shell;
+use std::io;
+
+use crate::cli::Cli;
+use crate::index;
+
+/// Writes a completion script for `shell` to stdout. The error codes rustc
+/// knows at the time are written into the script, titles and all, so codes
+/// complete without running rustc on every tab.
+pub fn generate(shell: Shell, bin_name: &str) {
+    let codes: Vec<PossibleValue> = index::build_index()
+        .into_iter()
+        .map(|entry| PossibleValue::new(entry.code).help(entry.title))
+        .collect();
+    let mut command = Cli::command()
+        .mut_arg("codes", |arg| {
+            arg.value_parser(PossibleValuesParser::new(codes.clone()))
+        })
+        .mut_arg("explain", |arg| {
+            arg.value_parser(PossibleValuesParser::new(codes))
+        });
+    clap_complete::generate(shell, &mut command, bin_name, &mut io::stdout());
+}
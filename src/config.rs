@@ -0,0 +1,95 @@
+//! The user's config file, `config.toml` in cargo-explain's config
+//! directory.
+
+use cargo_explain::Styles;
+use clap::ValueEnum;
+use std::convert::TryFrom;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use toml::Value;
+
+use crate::ColorWhen;
+
+/// `$XDG_CONFIG_HOME`, or `~/.config`.
+pub fn config_home() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| Some(PathBuf::from(env::var_os("HOME")?).join(".config")))
+}
+
+/// cargo-explain's own config directory, `$XDG_CONFIG_HOME/cargo-explain`
+/// or `~/.config/cargo-explain`.
+pub fn config_dir() -> Option<PathBuf> {
+    Some(config_home()?.join("cargo-explain"))
+}
+
+/// Settings from the config file. Flags override all of them.
+#[derive(Default)]
+pub struct Config {
+    pub theme: Option<String>,
+    pub width: Option<usize>,
+    /// `pager = "less -S"` pages through another command, and
+    /// `pager = false` turns paging off.
+    pub pager: Option<String>,
+    pub no_pager: bool,
+    pub color: Option<ColorWhen>,
+    /// The `[styles]` section, like `h1 = "bold purple"`.
+    pub styles: Styles,
+}
+
+/// Reads the config file, if there is one. Errors name the file and what's
+/// wrong with it.
+pub fn load() -> Result<Config, String> {
+    let path = match config_dir() {
+        Some(dir) => dir.join("config.toml"),
+        None => return Ok(Config::default()),
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(format!("couldn't read {}: {}", path.display(), e)),
+    };
+    parse(&text).map_err(|e| format!("in {}: {}", path.display(), e))
+}
+
+fn parse(text: &str) -> Result<Config, String> {
+    let table: toml::Table = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+    let mut config = Config::default();
+    for (key, value) in &table {
+        match (key.as_str(), value) {
+            ("theme", Value::String(theme)) => config.theme = Some(theme.clone()),
+            ("width", Value::Integer(width)) => {
+                let width = usize::try_from(*width)
+                    .ok()
+                    .filter(|&width| width > 0)
+                    .ok_or_else(|| format!("width should be a positive number, not {}", width))?;
+                config.width = Some(width);
+            }
+            ("pager", Value::String(pager)) => config.pager = Some(pager.clone()),
+            ("pager", Value::Boolean(pager)) => config.no_pager = !pager,
+            ("color", Value::String(color)) => {
+                let color = ColorWhen::from_str(color, false).map_err(|_| {
+                    format!("color should be auto, always, or never, not {:?}", color)
+                })?;
+                config.color = Some(color);
+            }
+            ("styles", Value::Table(styles)) => {
+                for (key, value) in styles {
+                    let value = value
+                        .as_str()
+                        .ok_or_else(|| format!("style {:?} should be a string", key))?;
+                    config.styles.set(key, value)?;
+                }
+            }
+            ("theme" | "color", _) => return Err(format!("{} should be a string", key)),
+            ("width", _) => return Err("width should be a number".to_string()),
+            ("pager", _) => return Err("pager should be a command or false".to_string()),
+            ("styles", _) => return Err("[styles] should be a table of styles".to_string()),
+            _ => return Err(format!("unknown setting {:?}", key)),
+        }
+    }
+    Ok(config)
+}
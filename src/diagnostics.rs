@@ -0,0 +1,240 @@
+//! Compiler diagnostics as cargo reports them with `--message-format=json`.
+
+use anstyle::{AnsiColor, Style};
+use cargo_explain::Paint;
+use serde_json::Value;
+use std::env;
+use std::io::{self, BufRead, BufReader};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use unicode_width::UnicodeWidthStr;
+
+/// One compiler message from a build.
+#[derive(Clone)]
+pub struct Diagnostic {
+    /// `error`, `warning`, and so on.
+    pub level: String,
+    /// The error code, like `E0308`, for messages that have one.
+    pub code: Option<String>,
+    /// The message as the compiler would print it.
+    pub rendered: Option<String>,
+    /// Where the message points, like `src/main.rs:6:18`.
+    pub location: Option<String>,
+    /// The source the message points at.
+    pub snippet: Option<Snippet>,
+}
+
+/// The lines of source a message points at, and what it says about them.
+#[derive(Clone)]
+pub struct Snippet {
+    /// The compiler's label for the span, like "expected `i32`, found
+    /// `&str`".
+    pub label: Option<String>,
+    pub lines: Vec<SnippetLine>,
+}
+
+#[derive(Clone)]
+pub struct SnippetLine {
+    pub number: usize,
+    pub text: String,
+    /// The characters pointed at, counting from 0.
+    pub highlight: Range<usize>,
+}
+
+/// More lines than this of a long span are left out.
+const MAX_SNIPPET_LINES: usize = 5;
+
+impl Diagnostic {
+    /// Reads a line of cargo's JSON output, or of rustc's own with
+    /// `--error-format=json`. Anything other than a compiler message, like
+    /// an artifact notice or a line that isn't JSON, is `None`.
+    pub fn parse(line: &str) -> Option<Diagnostic> {
+        let json: Value = serde_json::from_str(line).ok()?;
+        let message = if json["reason"] == "compiler-message" {
+            &json["message"]
+        } else if json["$message_type"] == "diagnostic" {
+            &json
+        } else {
+            return None;
+        };
+        let primary = message["spans"]
+            .as_array()?
+            .iter()
+            .find(|span| span["is_primary"] == true);
+        let location = primary.map(|span| {
+            format!(
+                "{}:{}:{}",
+                span["file_name"].as_str().unwrap_or_default(),
+                span["line_start"],
+                span["column_start"]
+            )
+        });
+        Some(Diagnostic {
+            level: message["level"].as_str()?.to_string(),
+            code: message["code"]["code"]
+                .as_str()
+                .filter(|code| is_error_code(code))
+                .map(str::to_string),
+            rendered: message["rendered"].as_str().map(str::to_string),
+            location,
+            snippet: primary.and_then(Snippet::parse),
+        })
+    }
+}
+
+impl Snippet {
+    fn parse(span: &Value) -> Option<Snippet> {
+        let first = span["line_start"].as_u64()? as usize;
+        let lines: Vec<SnippetLine> = span["text"]
+            .as_array()?
+            .iter()
+            .zip(first..)
+            .filter_map(|(line, number)| {
+                // Columns count characters from 1
+                let start = line["highlight_start"].as_u64()? as usize;
+                let end = line["highlight_end"].as_u64()? as usize;
+                Some(SnippetLine {
+                    number,
+                    text: line["text"].as_str()?.replace('\t', "    "),
+                    highlight: start.saturating_sub(1)..end.saturating_sub(1),
+                })
+            })
+            .collect();
+        if lines.is_empty() {
+            return None;
+        }
+        Some(Snippet {
+            label: span["label"].as_str().map(str::to_string),
+            lines,
+        })
+    }
+
+    /// The snippet as the compiler draws it, with line numbers in a gutter
+    /// and carets under what's pointed at, and the label after the last of
+    /// them. Without `styled`, it's plain text.
+    pub fn render(&self, location: &str, styled: bool, mono: bool) -> String {
+        let (gutter, mark) = match (styled, mono) {
+            (false, _) => (Style::new(), Style::new()),
+            (true, true) => (Style::new().bold(), Style::new().bold()),
+            (true, false) => (
+                AnsiColor::Blue.on_default().bold(),
+                AnsiColor::Red.on_default().bold(),
+            ),
+        };
+        let last = self.lines.len().min(MAX_SNIPPET_LINES);
+        let lines = &self.lines[..last];
+        let margin = " ".repeat(lines[last - 1].number.to_string().len());
+        let mut out = format!("{}{} {}\n", margin, gutter.paint("-->"), location);
+        out.push_str(&format!("{} {}\n", margin, gutter.paint("|")));
+        for (i, line) in lines.iter().enumerate() {
+            let before: String = line.text.chars().take(line.highlight.start).collect();
+            let marked: String = line
+                .text
+                .chars()
+                .skip(line.highlight.start)
+                .take(line.highlight.len())
+                .collect();
+            let after: String = line.text.chars().skip(line.highlight.end).collect();
+            let number = format!("{:>1$}", line.number, margin.len());
+            out.push_str(&format!(
+                "{} {} {}{}{}\n",
+                gutter.paint(number),
+                gutter.paint("|"),
+                before,
+                mark.paint(&marked),
+                after
+            ));
+            let mut carets = "^".repeat(marked.width().max(1));
+            if i == last - 1 {
+                if let Some(label) = &self.label {
+                    carets = format!("{} {}", carets, label);
+                }
+            }
+            out.push_str(&format!(
+                "{} {} {}{}\n",
+                margin,
+                gutter.paint("|"),
+                " ".repeat(before.width()),
+                mark.paint(carets)
+            ));
+        }
+        out
+    }
+}
+
+/// Whether `code` is an error code like `E0308`, rather than a lint name.
+fn is_error_code(code: &str) -> bool {
+    code.len() == 5 && code.starts_with('E') && code[1..].bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Whether the current directory is in a Cargo project.
+pub fn in_cargo_project() -> bool {
+    project_root().is_some()
+}
+
+/// The nearest directory, from the current one up, with a `Cargo.toml`.
+pub fn project_root() -> Option<PathBuf> {
+    let dir = env::current_dir().ok()?;
+    dir.ancestors()
+        .find(|dir| dir.join("Cargo.toml").is_file())
+        .map(Path::to_path_buf)
+}
+
+/// Where cargo builds the current project, which `CARGO_TARGET_DIR` or
+/// `build.target-dir` can move out of `target`, as `cargo metadata` says.
+pub fn target_dir() -> Option<PathBuf> {
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let output = Command::new(cargo)
+        .args(["metadata", "--format-version=1", "--no-deps"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let json: Value = serde_json::from_slice(&output.stdout).ok()?;
+    json["target_directory"].as_str().map(PathBuf::from)
+}
+
+/// Runs `cargo check` on the current project, returning the diagnostics it
+/// reports. Cargo's progress still goes to stderr.
+pub fn cargo_check() -> io::Result<Vec<Diagnostic>> {
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let mut child = Command::new(cargo)
+        .args(["check", "--message-format=json"])
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let stdout = child.stdout.take().expect("cargo's stdout is piped");
+    let mut diagnostics = Vec::new();
+    for line in BufReader::new(stdout).lines() {
+        diagnostics.extend(Diagnostic::parse(&line?));
+    }
+    child.wait()?;
+    Ok(diagnostics)
+}
+
+/// How often an error code came up in a build.
+pub struct CodeCount {
+    pub code: String,
+    pub count: usize,
+    /// The first error with the code.
+    pub first: Diagnostic,
+}
+
+/// Each distinct error code among errors, in order of first appearance.
+pub fn error_codes(diagnostics: &[Diagnostic]) -> Vec<CodeCount> {
+    let mut codes: Vec<CodeCount> = Vec::new();
+    for diagnostic in diagnostics.iter().filter(|d| d.level == "error") {
+        let code = match &diagnostic.code {
+            Some(code) => code,
+            None => continue,
+        };
+        match codes.iter_mut().find(|c| &c.code == code) {
+            Some(found) => found.count += 1,
+            None => codes.push(CodeCount {
+                code: code.clone(),
+                count: 1,
+                first: diagnostic.clone(),
+            }),
+        }
+    }
+    codes
+}
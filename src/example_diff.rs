@@ -0,0 +1,60 @@
+//! Marking what changed between a broken example and the fixed version
+//! that follows it.
+
+use crate::{Block, LineMark};
+
+/// The line marks for each of `blocks`, empty for all but code blocks.
+/// Code blocks are taken in pairs, the first of each a broken example and
+/// the second its fix.
+pub fn changes(blocks: &[Block]) -> Vec<Vec<Option<LineMark>>> {
+    let mut changes = vec![Vec::new(); blocks.len()];
+    let code: Vec<(usize, &str)> = blocks
+        .iter()
+        .enumerate()
+        .filter_map(|(i, block)| match block {
+            Block::CodeBlock(_, code) => Some((i, code.as_str())),
+            _ => None,
+        })
+        .collect();
+    for pair in code.chunks_exact(2) {
+        let ((i, before), (j, after)) = (pair[0], pair[1]);
+        let (removed, added) = diff_lines(before, after);
+        changes[i] = removed;
+        changes[j] = added;
+    }
+    changes
+}
+
+/// Marks the lines only in `before` as removed and the lines only in
+/// `after` as added, going by the longest run of lines they have in common.
+fn diff_lines(before: &str, after: &str) -> (Vec<Option<LineMark>>, Vec<Option<LineMark>>) {
+    let a: Vec<&str> = before.lines().map(str::trim_end).collect();
+    let b: Vec<&str> = after.lines().map(str::trim_end).collect();
+    // common[i][j] is how many lines a[i..] and b[j..] have in common
+    let mut common = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            common[i][j] = if a[i] == b[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let mut removed = vec![Some(LineMark::Removed); a.len()];
+    let mut added = vec![Some(LineMark::Added); b.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            removed[i] = None;
+            added[j] = None;
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    (removed, added)
+}
@@ -0,0 +1,107 @@
+//! `--future-incompat`: the lints behind cargo's report of dependencies
+//! that a future Rust will reject, by the dependency they're in.
+
+use regex::Regex;
+use std::collections::HashMap;
+
+use crate::{lints, toolchain};
+
+/// A lint, or an error code, that a dependency's code triggers.
+pub struct Finding {
+    /// The dependency, like `rental v0.5.5`.
+    pub package: String,
+    pub code: String,
+    pub count: usize,
+    /// The `-->` location of its first warning.
+    pub location: Option<String>,
+}
+
+/// Runs `cargo report future-incompatibilities` on the latest build.
+pub fn report() -> Result<String, String> {
+    let output = toolchain::cargo()
+        .args(["report", "future-incompatibilities", "--color", "never"])
+        .output()
+        .map_err(|e| format!("couldn't run cargo: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = stderr.lines().next().unwrap_or_default();
+        return Err(message.trim_start_matches("error: ").to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Finds each package's warnings in a report, in order of first appearance,
+/// and what lint each is by: the code in its header, the lint in its
+/// `#[warn(..)]` note, or, since those are capped away in dependencies, the
+/// lint whose documentation links to the issue it cites.
+pub fn findings(report: &str) -> Vec<Finding> {
+    let package_pattern = Regex::new(r"^The package `(\S+ \S+)[^`]*` currently triggers").unwrap();
+    let warning_pattern = Regex::new(r"^(?:warning|error)(?:\[(E\d{4})\])?:").unwrap();
+    let location_pattern = Regex::new(r"^\s*--> (\S+)").unwrap();
+    let note_pattern = Regex::new(r"#\[(?:allow|warn|deny|forbid)\((\w+)\)\]").unwrap();
+    let issue_pattern = Regex::new(r"rust-lang/rust/issues/(\d+)").unwrap();
+    let issues = lints::issues();
+
+    let mut found: Vec<Finding> = Vec::new();
+    let mut package = None;
+    // The warning being read: its code, if it's known yet, and location
+    let mut warning: Option<(Option<String>, Option<String>)> = None;
+    let mut finish = |package: &Option<String>, warning: Option<(_, _)>| {
+        let (package, code, location) = match (package, warning) {
+            (Some(package), Some((Some(code), location))) => (package, code, location),
+            _ => return,
+        };
+        match found
+            .iter_mut()
+            .find(|f| &f.package == package && f.code == code)
+        {
+            Some(finding) => finding.count += 1,
+            None => found.push(Finding {
+                package: package.clone(),
+                code,
+                count: 1,
+                location,
+            }),
+        }
+    };
+    for line in report.lines() {
+        if let Some(captures) = package_pattern.captures(line) {
+            finish(&package, warning.take());
+            package = Some(captures[1].to_string());
+            continue;
+        }
+        // The warnings themselves are quoted
+        let line = match line.strip_prefix('>') {
+            Some(line) => line.strip_prefix(' ').unwrap_or(line),
+            None => continue,
+        };
+        if let Some(captures) = warning_pattern.captures(line) {
+            finish(&package, warning.take());
+            warning = Some((captures.get(1).map(|code| code.as_str().to_string()), None));
+        } else if let Some((code, location)) = &mut warning {
+            if let Some(captures) = location_pattern.captures(line) {
+                location.get_or_insert_with(|| captures[1].to_string());
+            } else if let Some(captures) = note_pattern.captures(line) {
+                code.get_or_insert_with(|| captures[1].to_string());
+            } else if let Some(captures) = issue_pattern.captures(line) {
+                if let Some(lint) = issues.get(&captures[1]) {
+                    code.get_or_insert_with(|| lint.clone());
+                }
+            }
+        }
+    }
+    finish(&package, warning);
+    found
+}
+
+/// Every package each code was found in, for grouping by.
+pub fn packages_by_code(findings: &[Finding]) -> HashMap<String, String> {
+    let mut packages: HashMap<String, String> = HashMap::new();
+    for finding in findings {
+        packages
+            .entry(finding.code.clone())
+            .and_modify(|list| *list = format!("{}, {}", list, finding.package))
+            .or_insert_with(|| finding.package.clone());
+    }
+    packages
+}
@@ -0,0 +1,74 @@
+//! The decorative characters output is drawn with, in Unicode or plain
+//! ASCII for logs and terminals that can't show anything else.
+
+/// Every decorative character the renderer draws.
+pub struct Glyphs {
+    pub ellipsis: &'static str,
+    /// Rules under headers, and across the page for `---`.
+    pub rule: &'static str,
+    /// The rule under first level headers.
+    pub double_rule: &'static str,
+    /// The rule around group headers.
+    pub heavy_rule: &'static str,
+    /// Quote bars, gutters, and the sides of frames and tables.
+    pub bar: &'static str,
+    /// Bullets for each level of list nesting.
+    pub bullets: [&'static str; 3],
+    pub warning: &'static str,
+    pub untested: &'static str,
+    pub check: &'static str,
+    /// Separates details within a badge.
+    pub dot: &'static str,
+    /// Starts the rest of a code line that was too long for one.
+    pub continuation: &'static str,
+    /// Points out an error line in code.
+    pub pointer: &'static str,
+    /// A code frame's top left, top right, bottom left and bottom right.
+    pub frame_corners: [&'static str; 4],
+    /// A table's borders where rules meet: left, middle and right for the
+    /// top, the rule under the header, and the bottom.
+    pub table_joints: [[&'static str; 3]; 3],
+}
+
+pub const UNICODE: Glyphs = Glyphs {
+    ellipsis: "…",
+    rule: "─",
+    double_rule: "═",
+    heavy_rule: "━",
+    bar: "│",
+    bullets: ["•", "◦", "▪"],
+    warning: "⚠",
+    untested: "○",
+    check: "✓",
+    dot: "·",
+    continuation: "↪ ",
+    pointer: "▶",
+    frame_corners: ["╭", "╮", "╰", "╯"],
+    table_joints: [["┌", "┬", "┐"], ["├", "┼", "┤"], ["└", "┴", "┘"]],
+};
+
+pub const ASCII: Glyphs = Glyphs {
+    ellipsis: "...",
+    rule: "-",
+    double_rule: "=",
+    heavy_rule: "=",
+    bar: "|",
+    bullets: ["*", "-", "+"],
+    warning: "!",
+    untested: "o",
+    check: "+",
+    dot: "-",
+    continuation: "> ",
+    pointer: ">",
+    frame_corners: ["+", "+", "+", "+"],
+    table_joints: [["+", "+", "+"], ["+", "+", "+"], ["+", "+", "+"]],
+};
+
+/// The glyphs to draw with, ASCII ones if `ascii` is set.
+pub fn glyphs(ascii: bool) -> &'static Glyphs {
+    if ascii {
+        &ASCII
+    } else {
+        &UNICODE
+    }
+}
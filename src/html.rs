@@ -0,0 +1,130 @@
+//! The HTML of the Rust books and the error index, brought back to the
+//! markdown it was written in, for explanations that only come as HTML.
+
+use regex::{Captures, Regex};
+
+/// The part of a page under the heading with `id`, like `<h2 id="E0308">`,
+/// up to the next heading at its level or the end of the page's content.
+/// The heading itself is left out: it's only a name, which the code header
+/// says already.
+pub fn section<'a>(html: &'a str, tag: &str, id: &str) -> Option<&'a str> {
+    let start = html.find(&format!("<{} id=\"{}\"", tag, id))?;
+    let close = format!("</{}>", tag);
+    let body = &html[start..];
+    let body = &body[body.find(&close)? + close.len()..];
+    let end = body
+        .find(&format!("<{}", tag))
+        .or_else(|| body.find("</main>"))
+        .unwrap_or(body.len());
+    Some(&body[..end])
+}
+
+/// Converts the little HTML the books use back to markdown, with links
+/// made absolute from the URL of the page it's from.
+pub fn to_markdown(html: &str, page_url: &str) -> String {
+    // mdbook nests the playground's block in another, and rustdoc's error
+    // index put the block's attributes on the `pre`
+    let code_block = Regex::new(
+        r#"(?s)<pre(?: class="([^"]*)")?>(?:<pre class="playground">)?<code(?: class="([^"]*)")?>(.*?)</code></pre>(?:</pre>)?"#,
+    )
+    .unwrap();
+    // The playground's boilerplate, which rustdoc would hide
+    let boring = Regex::new(r#"(?s)<span class="boring">(.*?)</span>"#).unwrap();
+    let tags = Regex::new(r"</?[a-zA-Z][^>]*>").unwrap();
+    let mut markdown = String::new();
+    let mut last = 0;
+    for block in code_block.captures_iter(html) {
+        let whole = block.get(0).unwrap();
+        markdown.push_str(&inline_markdown(&html[last..whole.start()], page_url));
+        // Like `language-rust compile_fail E0308`, to `rust,compile_fail,E0308`
+        let classes = [block.get(1), block.get(2)];
+        let info: Vec<&str> = classes
+            .iter()
+            .flatten()
+            .flat_map(|classes| classes.as_str().split_whitespace())
+            .map(|class| class.trim_start_matches("language-"))
+            .filter(|&class| class != "playground" && class != "rust-example-rendered")
+            .collect();
+        let code = boring.replace_all(&block[3], |c: &Captures| {
+            c[1].lines()
+                .map(|line| format!("# {}\n", line))
+                .collect::<String>()
+        });
+        let code = decode_entities(&tags.replace_all(&code, ""));
+        markdown.push_str(&format!(
+            "\n```{}\n{}\n```\n\n",
+            info.join(","),
+            code.trim_end()
+        ));
+        last = whole.end();
+    }
+    markdown.push_str(&inline_markdown(&html[last..], page_url));
+    let blank_lines = Regex::new(r"\n{3,}").unwrap();
+    blank_lines
+        .replace_all(markdown.trim(), "\n\n")
+        .into_owned()
+        + "\n"
+}
+
+fn inline_markdown(html: &str, page_url: &str) -> String {
+    let links = Regex::new(r#"<a href="([^"]*)">"#).unwrap();
+    let mut text = links
+        .replace_all(html, |c: &Captures| {
+            format!("<a href=\"{}\">", absolute_link(&c[1], page_url))
+        })
+        .into_owned();
+    let rules = [
+        // The playground's "Run" button and the error index's tooltips
+        (r#"(?s)<a class="test-arrow".*?</a>"#, ""),
+        (r"(?s)<div class='tooltip[^']*'>.*?</div>", ""),
+        (
+            r"(?s)<h3[^>]*>(?:<a[^>]*>)?(.*?)(?:</a>)?</h3>",
+            "\n### ${1}\n\n",
+        ),
+        (
+            r"(?s)<h4[^>]*>(?:<a[^>]*>)?(.*?)(?:</a>)?</h4>",
+            "\n#### ${1}\n\n",
+        ),
+        (r"(?s)<p>(.*?)</p>", "${1}\n\n"),
+        (r"(?s)<li>\s*(.*?)\s*</li>", "* ${1}\n"),
+        (r"(?s)<code>(.*?)</code>", "`${1}`"),
+        (r#"(?s)<a href="([^"]*)">(.*?)</a>"#, "[${2}](${1})"),
+        (r"(?s)<strong>(.*?)</strong>", "**${1}**"),
+        (r"(?s)<em>(.*?)</em>", "*${1}*"),
+        (r"</?[a-zA-Z][^>]*>", ""),
+    ];
+    for (pattern, replacement) in rules {
+        text = Regex::new(pattern)
+            .unwrap()
+            .replace_all(&text, replacement)
+            .into_owned();
+    }
+    decode_entities(&text)
+}
+
+/// Links between pages are relative to the page they're on.
+fn absolute_link(href: &str, page_url: &str) -> String {
+    if href.contains("://") {
+        return href.to_string();
+    }
+    if href.starts_with('#') {
+        let page = page_url.split('#').next().unwrap_or(page_url);
+        return format!("{}{}", page, href);
+    }
+    let mut base: Vec<&str> = page_url.split('/').collect();
+    base.pop();
+    let mut href = href;
+    while let Some(rest) = href.strip_prefix("../") {
+        base.pop();
+        href = rest;
+    }
+    format!("{}/{}", base.join("/"), href)
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
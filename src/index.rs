@@ -0,0 +1,252 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::{bundled, toolchain};
+
+/// rustc's error codes are allocated sequentially from E0001, and haven't
+/// come close to running past E0999 yet.
+const MAX_ERROR_CODE: usize = 999;
+/// Most codes rustc is asked about when looking for ones near a mistyped
+/// code.
+const MAX_SUGGESTION_PROBES: usize = 12;
+/// Most codes rustc is asked about for suggestions without a bundled index
+/// to skip the unexplained ones, which are most near codes.
+const MAX_UNBUNDLED_PROBES: usize = 40;
+/// Most codes newer than the bundled index's last that rustc is asked
+/// about. Only a rustc years newer has added more.
+const MAX_NEWER_CODES: usize = 50;
+
+pub struct IndexEntry {
+    pub code: String,
+    pub title: String,
+    pub has_explanation: bool,
+    pub explanation: String,
+}
+
+/// The first sentence of an explanation, which rustc writes as a summary.
+pub fn title_of(explanation: &str) -> String {
+    let paragraph = explanation
+        .lines()
+        .skip_while(|line| line.trim().is_empty() || line.starts_with('#'))
+        .take_while(|line| !line.trim().is_empty())
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join(" ");
+    match paragraph.find(". ") {
+        Some(end) => paragraph[..=end].to_string(),
+        None => paragraph,
+    }
+}
+
+/// Broad categories for error codes, picked by the first keyword found in a
+/// code's title.
+const CATEGORIES: &[(&str, &[&str])] = &[
+    (
+        "Ownership and borrowing",
+        &["borrow", "moved", "move", "ownership", "reference"],
+    ),
+    ("Lifetimes", &["lifetime", "outlive", "'static"]),
+    ("Traits", &["trait", "impl", "object safe", "dyn"]),
+    ("Patterns", &["pattern", "match arm", "binding"]),
+    (
+        "Generics",
+        &["generic", "type parameter", "const parameter"],
+    ),
+    ("Macros and attributes", &["macro", "attribute", "#["]),
+    (
+        "Modules and imports",
+        &["import", "module", "crate", "private", "visibility", "use "],
+    ),
+    ("Types", &["type", "mismatch", "cast", "size"]),
+];
+
+/// Classifies an error code by its title.
+pub fn category_of(title: &str) -> &'static str {
+    let title = title.to_lowercase();
+    CATEGORIES
+        .iter()
+        .find(|(_, keywords)| keywords.iter().any(|keyword| title.contains(keyword)))
+        .map_or("Other", |(category, _)| category)
+}
+
+/// Asks rustc about a single code. Returns `None` if rustc doesn't consider
+/// it a valid error code at all.
+pub fn probe(code: &str) -> Option<IndexEntry> {
+    if toolchain::without_rustc() {
+        return toolchain::stored_entry(code);
+    }
+    let output = match toolchain::rustc().args(["--explain", code]).output() {
+        Ok(output) => output,
+        Err(e) if toolchain::fall_back_to_bundle(&e) => return toolchain::stored_entry(code),
+        Err(_) => return None,
+    };
+    if output.status.success() {
+        let explanation = String::from_utf8_lossy(&output.stdout);
+        return Some(IndexEntry {
+            code: code.to_string(),
+            title: title_of(&explanation),
+            has_explanation: true,
+            explanation: explanation.into_owned(),
+        });
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("no extended information") {
+        Some(IndexEntry {
+            code: code.to_string(),
+            title: String::new(),
+            has_explanation: false,
+            explanation: String::new(),
+        })
+    } else {
+        None
+    }
+}
+
+/// Probes every possible error code, spreading the rustc invocations across
+/// one worker thread per available core. Entries are sorted by code.
+pub fn build_index() -> Vec<IndexEntry> {
+    let next = AtomicUsize::new(1);
+    let entries = Mutex::new(Vec::new());
+    let workers = thread::available_parallelism().map_or(4, |n| n.get());
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let n = next.fetch_add(1, Ordering::Relaxed);
+                if n > MAX_ERROR_CODE {
+                    break;
+                }
+                if let Some(entry) = probe(&format!("E{:04}", n)) {
+                    entries.lock().unwrap().push(entry);
+                }
+            });
+        }
+    });
+
+    let mut entries = entries.into_inner().unwrap();
+    entries.sort_by(|a, b| a.code.cmp(&b.code));
+    entries
+}
+
+pub fn index_json(entries: &[IndexEntry]) -> serde_json::Value {
+    let map = entries
+        .iter()
+        .map(|entry| {
+            (
+                entry.code.clone(),
+                serde_json::json!({
+                    "title": entry.title,
+                    "has_explanation": entry.has_explanation,
+                }),
+            )
+        })
+        .collect();
+    serde_json::Value::Object(map)
+}
+
+/// Edit distance between two codes, counting inserted, deleted and changed
+/// characters.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let changed = previous[j] + (ca != cb) as usize;
+            current.push(changed.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Explained codes within two edits of `code`, nearest first and, among
+/// equally near ones, closest in number. At most three are returned.
+///
+/// Rather than building the whole index, only the nearest codes are asked
+/// about, skipping those the bundled index says have no explanation, so a
+/// typo costs a handful of rustc runs. Without a bundled index more are
+/// asked about, and a suggestion can still be missed.
+pub fn suggestions(code: &str) -> Vec<String> {
+    let number = code.trim_start_matches('E').parse::<usize>().ok();
+    let mut near: Vec<(usize, usize, String)> = (1..=MAX_ERROR_CODE)
+        .map(|other| {
+            let candidate = format!("E{:04}", other);
+            let apart = number.map_or(0, |number| number.abs_diff(other));
+            (edit_distance(code, &candidate), apart, candidate)
+        })
+        .filter(|(distance, _, candidate)| *distance <= 2 && candidate != code)
+        .collect();
+    near.sort();
+    let bundled = bundled::version().is_some();
+    let probes = if bundled {
+        MAX_SUGGESTION_PROBES
+    } else {
+        MAX_UNBUNDLED_PROBES
+    };
+    near.into_iter()
+        .map(|(_, _, candidate)| candidate)
+        .filter(|candidate| {
+            !bundled || bundled::probe(candidate).is_some_and(|e| e.has_explanation)
+        })
+        .take(probes)
+        .filter(|candidate| probe(candidate).is_some_and(|entry| entry.has_explanation))
+        .take(3)
+        .collect()
+}
+
+/// The last code rustc knows, going by the bundled index and then asking
+/// rustc about the codes after it, or before it for an older rustc. `None`
+/// without a bundled index.
+pub fn last_code() -> Option<String> {
+    let bundled_last = bundled::last_code()?[1..].parse::<usize>().ok()?;
+    let mut last = bundled_last;
+    let known = |n: usize| probe(&format!("E{:04}", n)).is_some();
+    if known(last) {
+        let newest = MAX_ERROR_CODE.min(bundled_last + MAX_NEWER_CODES);
+        while last < newest && known(last + 1) {
+            last += 1;
+        }
+    } else {
+        while last > 1 && !known(last) {
+            last -= 1;
+        }
+    }
+    Some(format!("E{:04}", last))
+}
+
+/// Words too common in explanations to say anything about a match.
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "be", "by", "for", "in", "is", "it", "of", "on", "or", "the", "this",
+    "to", "was", "with",
+];
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .map(str::to_lowercase)
+        .filter(|word| !word.is_empty() && !STOP_WORDS.contains(&word.as_str()))
+        .collect()
+}
+
+/// Finds the entry whose title and explanation share the most words with
+/// `message`. Title hits count double. The score is the fraction of the
+/// best possible score, from 0 to 1.
+pub fn best_match<'a>(entries: &'a [IndexEntry], message: &str) -> Option<(&'a IndexEntry, f64)> {
+    let query = words(message);
+    if query.is_empty() {
+        return None;
+    }
+    entries
+        .iter()
+        .map(|entry| {
+            let title = words(&entry.title);
+            let body = words(&entry.explanation);
+            let hits: usize = query
+                .iter()
+                .map(|word| 2 * title.contains(word) as usize + body.contains(word) as usize)
+                .sum();
+            (entry, hits as f64 / (3 * query.len()) as f64)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+}
@@ -0,0 +1,142 @@
+//! Structured output, for tools that want an explanation's content rather
+//! than terminal text.
+//!
+//! An explanation is an array of block objects. Each has a `"type"` and,
+//! with `--json-render`, a `"rendered"` string holding its ANSI output.
+//!
+//! | `type`           | fields                                           |
+//! |------------------|--------------------------------------------------|
+//! | `header`         | `level` (1-6), `spans`                           |
+//! | `paragraph`      | `spans`                                          |
+//! | `blockquote`     | `blocks`                                         |
+//! | `code`           | `language` (string or null), `code`              |
+//! | `ordered_list`   | `start`, `loose`, `items`                        |
+//! | `unordered_list` | `loose`, `items`                                 |
+//! | `table`          | `header`, `align`, `rows` (cells as spans)       |
+//! | `raw`            | `text`                                           |
+//! | `rule`           |                                                  |
+//!
+//! List items are objects with either `spans` or `blocks`. Spans are
+//! objects with a `"type"` too:
+//!
+//! | `type`     | fields                                      |
+//! |------------|---------------------------------------------|
+//! | `text`     | `text`                                      |
+//! | `code`     | `code`                                      |
+//! | `link`     | `spans`, `url`, `title` (string or null)    |
+//! | `image`    | `alt`, `url`, `title` (string or null)      |
+//! | `emphasis` | `spans`                                     |
+//! | `strong`   | `spans`                                     |
+//! | `break`    |                                             |
+
+use crate::{
+    parse_blocks, render_each, Align, Block, ListItem, RenderOptions, Renderer, Span, Table,
+};
+use serde_json::{json, Value};
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+
+/// Converts an explanation to JSON blocks, optionally with each top-level
+/// block's rendered terminal output alongside.
+pub fn render_json(
+    input: &str,
+    ps: &SyntaxSet,
+    ts: &ThemeSet,
+    options: &RenderOptions,
+    with_rendered: bool,
+) -> Value {
+    let blocks = parse_blocks(input, options);
+    let mut values: Vec<Value> = blocks.iter().map(block_json).collect();
+    if with_rendered {
+        let renderer = Renderer::new(ps, ts, options, None);
+        for (value, (_, rendered)) in values.iter_mut().zip(render_each(blocks, &renderer)) {
+            value["rendered"] = Value::String(rendered);
+        }
+    }
+    Value::Array(values)
+}
+
+fn blocks_json(blocks: &[Block]) -> Value {
+    Value::Array(blocks.iter().map(block_json).collect())
+}
+
+fn block_json(block: &Block) -> Value {
+    match block {
+        Block::Header(spans, level) => {
+            json!({ "type": "header", "level": level, "spans": spans_json(spans) })
+        }
+        Block::Paragraph(spans) => json!({ "type": "paragraph", "spans": spans_json(spans) }),
+        Block::Blockquote(blocks) => json!({ "type": "blockquote", "blocks": blocks_json(blocks) }),
+        Block::Table(table) => table_json(table),
+        Block::CodeBlock(language, code) => {
+            json!({ "type": "code", "language": language, "code": code })
+        }
+        Block::List(list) => match list.start {
+            Some(start) => json!({
+                "type": "ordered_list",
+                "start": start,
+                "loose": list.loose,
+                "items": items_json(&list.items),
+            }),
+            None => json!({
+                "type": "unordered_list",
+                "loose": list.loose,
+                "items": items_json(&list.items),
+            }),
+        },
+        Block::Raw(text) => json!({ "type": "raw", "text": text }),
+        Block::Hr => json!({ "type": "rule" }),
+    }
+}
+
+fn table_json(table: &Table) -> Value {
+    let align: Vec<&str> = table
+        .align
+        .iter()
+        .map(|align| match align {
+            Align::Left => "left",
+            Align::Center => "center",
+            Align::Right => "right",
+        })
+        .collect();
+    let cells_json = |cells: &[Vec<Span>]| -> Vec<Value> {
+        cells.iter().map(|spans| spans_json(spans)).collect()
+    };
+    json!({
+        "type": "table",
+        "header": cells_json(&table.header),
+        "align": align,
+        "rows": table.rows.iter().map(|row| cells_json(row)).collect::<Vec<_>>(),
+    })
+}
+
+fn items_json(items: &[ListItem]) -> Value {
+    let items = items
+        .iter()
+        .map(|item| match item {
+            ListItem::Simple(spans) => json!({ "spans": spans_json(spans) }),
+            ListItem::Paragraph(blocks) => json!({ "blocks": blocks_json(blocks) }),
+        })
+        .collect();
+    Value::Array(items)
+}
+
+fn spans_json(spans: &[Span]) -> Value {
+    Value::Array(spans.iter().map(span_json).collect())
+}
+
+fn span_json(span: &Span) -> Value {
+    match span {
+        Span::Break => json!({ "type": "break" }),
+        Span::Text(text) => json!({ "type": "text", "text": text }),
+        Span::Code(code) => json!({ "type": "code", "code": code }),
+        Span::Link(spans, url, title) => {
+            json!({ "type": "link", "spans": spans_json(spans), "url": url, "title": title })
+        }
+        Span::Image(alt, url, title) => {
+            json!({ "type": "image", "alt": alt, "url": url, "title": title })
+        }
+        Span::Emphasis(spans) => json!({ "type": "emphasis", "spans": spans_json(spans) }),
+        Span::Strong(spans) => json!({ "type": "strong", "spans": spans_json(spans) }),
+    }
+}
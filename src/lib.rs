@@ -0,0 +1,1929 @@
+//! Syntax highlighting terminal renderer for `rustc --explain` markdown.
+
+mod ast;
+#[cfg(feature = "bat")]
+mod bat;
+mod example_diff;
+mod glyphs;
+mod json;
+mod styles;
+mod table;
+mod text_diff;
+mod theme_diff;
+
+use anstyle::{AnsiColor, Style};
+use hyphenation::{Hyphenator, Language, Load, Standard};
+use regex::Regex;
+use std::borrow::Cow;
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{
+    Color as SyntectColor, FontStyle, Highlighter, Style as SyntectStyle, StyleModifier, Theme,
+    ThemeItem, ThemeSet,
+};
+use syntect::parsing::{Scope, SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+use textwrap::{WordSeparator, WordSplitter, WrapAlgorithm};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+pub use ast::{Block, List, ListItem, Span};
+pub use glyphs::{glyphs, Glyphs};
+pub use json::render_json;
+pub use styles::{parse_style, Paint, Styles};
+pub use table::{Align, Table};
+pub use text_diff::diff_words;
+pub use theme_diff::diff_themes;
+
+/// The syntect theme code is highlighted with unless another is chosen.
+pub const DEFAULT_THEME: &str = "base16-eighties.dark";
+/// The default theme on terminals with a light background.
+pub const DEFAULT_LIGHT_THEME: &str = "InspiredGitHub";
+pub const ANSI_RESET: &str = "\x1B[0m";
+pub const DEFAULT_INDENT: usize = 2;
+/// Width prose is wrapped to when the terminal's isn't known.
+pub const DEFAULT_WIDTH: usize = 80;
+/// Nested blocks are never wrapped narrower than this, however deep.
+const MIN_WRAP_WIDTH: usize = 20;
+
+/// A hook run on every block as it's rendered.
+pub type PostProcess<'a> = &'a dyn Fn(Block) -> Block;
+
+/// Settings that change how an explanation is rendered.
+pub struct RenderOptions {
+    /// Name of the syntect theme code is highlighted with.
+    pub theme: String,
+    pub code_bg: CodeBackground,
+    pub inline_code: InlineCode,
+    /// Columns added per level of blockquote or list nesting.
+    pub indent: usize,
+    /// Blank lines between blocks.
+    pub spacing: Spacing,
+    /// Column prose is wrapped at.
+    pub width: usize,
+    /// Join adjacent code blocks that share a language.
+    pub merge_code: bool,
+    /// Start with a table of contents built from the headers.
+    pub toc: bool,
+    /// Start with a table of contents anyway when the rest runs longer than
+    /// this many lines.
+    pub toc_after_lines: Option<usize>,
+    /// Number headers by section, like `1.` and `1.1`.
+    pub numbered: bool,
+    /// Pad code lines with background color so blocks come out rectangular.
+    pub pad_code: bool,
+    /// Cut explanations off after this many top-level blocks.
+    pub max_blocks: Option<usize>,
+    /// Use only bold, italic, and underline, never color.
+    pub mono: bool,
+    /// Show the lines rustdoc hides from examples, dimmed, instead of
+    /// leaving them out.
+    pub show_hidden: bool,
+    /// Draw a rule under top-level headers.
+    pub header_rules: bool,
+    /// Hyphenate words to fill lines out more evenly.
+    pub hyphenate: bool,
+    /// Number the lines of code blocks in a gutter down their left side.
+    pub line_numbers: bool,
+    /// Mark the lines that change between each pair of examples, taking
+    /// them to be a broken example and its fix.
+    pub diff_examples: bool,
+    /// Draw a frame around code blocks, titled with their language.
+    pub frame_code: bool,
+    /// Lay prose and code out side by side across this many columns.
+    pub columns: Option<usize>,
+    /// Emit links as OSC 8 hyperlinks instead of spelling out their URLs.
+    pub hyperlinks: bool,
+    /// How many colors the terminal can show; theme colors are brought down
+    /// to the nearest it has.
+    pub color_depth: ColorDepth,
+    /// The user's own styles for prose.
+    pub styles: Styles,
+    /// Draw bullets, rules, borders and badges in plain ASCII.
+    pub ascii: bool,
+    /// Highlight code blocks with `bat` when it's installed.
+    #[cfg(feature = "bat")]
+    pub use_bat: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            theme: DEFAULT_THEME.to_string(),
+            code_bg: CodeBackground::Theme,
+            inline_code: InlineCode::Tint,
+            indent: DEFAULT_INDENT,
+            spacing: Spacing::default(),
+            width: DEFAULT_WIDTH,
+            merge_code: false,
+            toc: false,
+            toc_after_lines: None,
+            numbered: false,
+            pad_code: false,
+            max_blocks: None,
+            mono: false,
+            show_hidden: false,
+            header_rules: false,
+            hyphenate: false,
+            line_numbers: false,
+            diff_examples: false,
+            frame_code: false,
+            columns: None,
+            hyperlinks: false,
+            color_depth: ColorDepth::TrueColor,
+            styles: Styles::default(),
+            ascii: false,
+            #[cfg(feature = "bat")]
+            use_bat: false,
+        }
+    }
+}
+
+/// Renders `rustc --explain` markdown for the terminal, highlighting each
+/// code block in the language its fence names. Fences with no language, or
+/// with only rustdoc attributes like `compile_fail,E0308`, are highlighted
+/// as Rust, and languages syntect doesn't know are left plain.
+///
+/// If given, `post_process` is called on every block after the built-in
+/// styling has been applied to it. Blocks are visited in document order,
+/// but the blocks nested inside a blockquote or list item are visited
+/// before their container, which by then has been flattened into a
+/// `Block::Raw` of rendered text. Highlighted code blocks are also passed on
+/// as `Block::Raw`, since they're preformatted and must never be rewrapped.
+pub fn render(
+    input: &str,
+    ps: &SyntaxSet,
+    ts: &ThemeSet,
+    options: &RenderOptions,
+    post_process: Option<PostProcess>,
+) -> String {
+    let renderer = Renderer::new(ps, ts, options, post_process);
+    let mut blox = parse_blocks(input, options);
+    let numbers = if options.numbered {
+        section_numbers(&blox)
+    } else {
+        Vec::new()
+    };
+    let omitted = match options.max_blocks {
+        Some(max_blocks) => truncate_blocks(&mut blox, max_blocks),
+        None => 0,
+    };
+    // Only the sections left after truncating are listed
+    let toc = if options.toc || options.toc_after_lines.is_some() {
+        table_of_contents(&blox, &numbers, &renderer)
+    } else {
+        None
+    };
+    number_headers(&mut blox, &numbers);
+    let mut body = match options.columns {
+        Some(width) => render_columns(blox, &renderer, width),
+        None => render_blocks(blox, &renderer),
+    };
+    if omitted > 0 {
+        let plural = if omitted == 1 { "" } else { "s" };
+        let hint = format!(
+            "{} {} more section{}; use --full or --open to read all.",
+            glyphs(options.ascii).ellipsis,
+            omitted,
+            plural
+        );
+        body.push_str(&format!(
+            "\n\n{}",
+            Style::new().dimmed().italic().paint(hint)
+        ));
+    }
+    let long = options
+        .toc_after_lines
+        .is_some_and(|lines| body.lines().count() > lines);
+    match toc {
+        Some(toc) if options.toc || long => format!("{}\n\n{}", toc, body),
+        _ => body,
+    }
+}
+
+/// Parses an explanation, applying any options that restructure blocks
+/// before they're rendered.
+fn parse_blocks(input: &str, options: &RenderOptions) -> Vec<Block> {
+    let blox = ast::parse(&sanitize(input));
+    if options.merge_code {
+        merge_code_blocks(blox)
+    } else {
+        blox
+    }
+}
+
+/// The other error codes an explanation's prose mentions, like "see also
+/// E0277", in order of first mention. Code examples aren't searched, and
+/// `own_code` is left out.
+pub fn related_codes(input: &str, own_code: &str) -> Vec<String> {
+    fn prose(blocks: &[Block], text: &mut String) {
+        for block in blocks {
+            match block {
+                Block::Header(spans, _) | Block::Paragraph(spans) => {
+                    text.push_str(&plain_text(spans));
+                }
+                Block::Blockquote(blocks) => prose(blocks, text),
+                Block::List(list) => {
+                    for item in &list.items {
+                        match item {
+                            ListItem::Simple(spans) => text.push_str(&plain_text(spans)),
+                            ListItem::Paragraph(blocks) => prose(blocks, text),
+                        }
+                    }
+                }
+                Block::Table(table) => {
+                    for cell in table.header.iter().chain(table.rows.iter().flatten()) {
+                        text.push_str(&plain_text(cell));
+                        text.push(' ');
+                    }
+                }
+                Block::CodeBlock(..) | Block::Raw(_) | Block::Hr => {}
+            }
+            text.push('\n');
+        }
+    }
+    let mut text = String::new();
+    prose(&ast::parse(&sanitize(input)), &mut text);
+    let pattern = Regex::new(r"\bE\d{4}\b").unwrap();
+    let mut codes: Vec<String> = Vec::new();
+    for code in pattern.find_iter(&text) {
+        let code = code.as_str();
+        if code != own_code && !codes.iter().any(|c| c == code) {
+            codes.push(code.to_string());
+        }
+    }
+    codes
+}
+
+/// Makes text safe to print to a terminal. Control characters other than
+/// newlines and tabs are shown in caret notation (`^[` for escape) rather
+/// than passed through, and invisible formatting characters that can hide
+/// or reorder text are dropped. The zero-width joiner stays, since emoji
+/// sequences are built with it.
+fn sanitize(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    for c in input.replace("\r\n", "\n").chars() {
+        match c {
+            '\n' | '\t' => output.push(c),
+            '\u{0}'..='\u{1F}' => {
+                output.push('^');
+                output.push((c as u8 + 0x40) as char);
+            }
+            '\u{7F}' => output.push_str("^?"),
+            '\u{80}'..='\u{9F}'
+            | '\u{200B}'
+            | '\u{200C}'
+            | '\u{200E}'
+            | '\u{200F}'
+            | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'..='\u{2069}'
+            | '\u{FEFF}' => {}
+            _ => output.push(c),
+        }
+    }
+    output
+}
+
+/// Drops everything after the first `max_blocks` blocks, backing up to the
+/// start of a section if that would leave one cut short. Returns how many
+/// sections were dropped.
+fn truncate_blocks(blocks: &mut Vec<Block>, max_blocks: usize) -> usize {
+    if blocks.len() <= max_blocks {
+        return 0;
+    }
+    let is_header = |block: &Block| matches!(block, Block::Header(..));
+    let mut cut = max_blocks;
+    if !is_header(&blocks[cut]) {
+        if let Some(section_start) = (1..cut).rev().find(|&i| is_header(&blocks[i])) {
+            cut = section_start;
+        }
+    }
+    let rest = blocks.split_off(cut);
+    let headers = rest.iter().filter(|block| is_header(block)).count();
+    if is_header(&rest[0]) {
+        headers
+    } else {
+        headers + 1
+    }
+}
+
+/// Explanations with fewer headers than this don't get a table of contents.
+const MIN_TOC_HEADERS: usize = 3;
+
+/// The text of some spans without any markup.
+fn plain_text(spans: &[Span]) -> String {
+    spans
+        .iter()
+        .map(|span| match span {
+            Span::Break => " ".to_string(),
+            Span::Text(text) | Span::Code(text) => text.clone(),
+            Span::Link(spans, _, _) => plain_text(spans),
+            Span::Image(alt, _, _) => alt.clone(),
+            Span::Emphasis(spans) | Span::Strong(spans) => plain_text(spans),
+        })
+        .collect()
+}
+
+/// A stable identifier for a header, in the style of rustdoc's anchors.
+fn header_id(text: &str) -> String {
+    let mut id = String::new();
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            id.extend(c.to_lowercase());
+        } else if (c.is_whitespace() || c == '-') && !id.ends_with('-') && !id.is_empty() {
+            id.push('-');
+        }
+    }
+    id.trim_end_matches('-').to_string()
+}
+
+/// Hierarchical numbers for each top-level header, like `2.` for a section
+/// and `2.1` for its first subsection, counting from the shallowest level
+/// used. A skipped level counts as 0.
+fn section_numbers(blocks: &[Block]) -> Vec<String> {
+    let levels: Vec<usize> = blocks
+        .iter()
+        .filter_map(|block| match block {
+            Block::Header(_, level) => Some(*level),
+            _ => None,
+        })
+        .collect();
+    let top = levels.iter().copied().min().unwrap_or(1);
+    let mut counters: Vec<usize> = Vec::new();
+    levels
+        .into_iter()
+        .map(|level| {
+            let depth = level - top;
+            counters.resize(depth + 1, 0);
+            counters[depth] += 1;
+            match counters.as_slice() {
+                [n] => format!("{}.", n),
+                _ => counters
+                    .iter()
+                    .map(usize::to_string)
+                    .collect::<Vec<_>>()
+                    .join("."),
+            }
+        })
+        .collect()
+}
+
+/// Puts each top-level header's section number in front of its text.
+fn number_headers(blocks: &mut [Block], numbers: &[String]) {
+    let headers = blocks.iter_mut().filter_map(|block| match block {
+        Block::Header(spans, _) => Some(spans),
+        _ => None,
+    });
+    for (spans, number) in headers.zip(numbers) {
+        spans.insert(0, Span::Text(format!("{} ", number)));
+    }
+}
+
+/// Lists the headers in their header colors, numbered with `numbers` if
+/// there are any.
+fn table_of_contents(blocks: &[Block], numbers: &[String], r: &Renderer) -> Option<String> {
+    let headers: Vec<(usize, String)> = blocks
+        .iter()
+        .filter_map(|block| match block {
+            Block::Header(spans, level) => Some((*level, plain_text(spans))),
+            _ => None,
+        })
+        .collect();
+    if headers.len() < MIN_TOC_HEADERS {
+        return None;
+    }
+    let top = headers.iter().map(|(level, _)| *level).min().unwrap_or(1);
+    let mut toc = Style::new().bold().underline().paint("Contents");
+    for (i, (level, text)) in headers.into_iter().enumerate() {
+        let indent = " ".repeat((level - top + 1) * r.options.indent);
+        let id = Style::new()
+            .dimmed()
+            .paint(format!("#{}", header_id(&text)));
+        let number = numbers.get(i).map_or(String::new(), |n| format!("{} ", n));
+        let (color, _) = HEADER_STYLES[(level - top).min(HEADER_STYLES.len() - 1)];
+        let entry = accent(color, r).paint(format!("{}{}", number, text));
+        toc.push_str(&format!("\n{}{} {}", indent, entry, id));
+    }
+    Some(toc)
+}
+
+/// Everything the block and span mappers need to highlight and style output.
+#[derive(Clone)]
+struct Renderer<'a> {
+    syntax: &'a SyntaxReference,
+    ps: &'a SyntaxSet,
+    theme: Cow<'a, Theme>,
+    options: &'a RenderOptions,
+    post_process: Option<PostProcess<'a>>,
+    /// Column prose is wrapped at.
+    wrap_width: usize,
+    /// How many lists the blocks being rendered are nested in.
+    list_depth: usize,
+    /// How each line of the code block being rendered changed from or to
+    /// the example it's paired with, by `--diff-examples`.
+    line_changes: Vec<Option<LineMark>>,
+}
+
+impl<'a> Renderer<'a> {
+    fn new(
+        ps: &'a SyntaxSet,
+        ts: &'a ThemeSet,
+        options: &'a RenderOptions,
+        post_process: Option<PostProcess<'a>>,
+    ) -> Renderer<'a> {
+        Renderer {
+            syntax: ps.find_syntax_by_extension("rs").unwrap(),
+            ps,
+            theme: if options.mono {
+                Cow::Owned(mono_theme())
+            } else {
+                let theme = find_theme(ts, &options.theme);
+                Cow::Borrowed(theme.unwrap_or(&ts.themes[DEFAULT_THEME]))
+            },
+            options,
+            post_process,
+            wrap_width: options.width.max(MIN_WRAP_WIDTH),
+            list_depth: 0,
+            line_changes: Vec::new(),
+        }
+    }
+
+    pub(crate) fn glyphs(&self) -> &'static Glyphs {
+        glyphs(self.options.ascii)
+    }
+
+    /// A renderer for blocks indented `by` more columns.
+    fn narrowed(&self, by: usize) -> Renderer<'a> {
+        Renderer {
+            wrap_width: self.wrap_width.saturating_sub(by).max(MIN_WRAP_WIDTH),
+            ..self.clone()
+        }
+    }
+}
+
+/// How many blank lines are left between blocks.
+#[derive(Clone, Copy)]
+pub struct Spacing {
+    /// Between paragraphs, lists, quotes, and the like.
+    pub blocks: usize,
+    /// Before and after code blocks. Code always starts with a line of its
+    /// background color, which counts as the first.
+    pub code: usize,
+    /// Before headers.
+    pub headers: usize,
+}
+
+impl Default for Spacing {
+    fn default() -> Spacing {
+        Spacing {
+            blocks: 1,
+            code: 1,
+            headers: 1,
+        }
+    }
+}
+
+impl Spacing {
+    /// Parses a list like `blocks=1,code=0,headers=2`. Anything left out
+    /// keeps its default.
+    pub fn parse(value: &str) -> Option<Spacing> {
+        let mut spacing = Spacing::default();
+        for setting in value.split(',') {
+            let (key, lines) = setting.split_once('=')?;
+            let lines = lines.trim().parse().ok()?;
+            match key.trim() {
+                "blocks" => spacing.blocks = lines,
+                "code" => spacing.code = lines,
+                "headers" => spacing.headers = lines,
+                _ => return None,
+            }
+        }
+        Some(spacing)
+    }
+}
+
+/// Looks up a theme by name, failing with a list of the themes there are.
+pub fn find_theme<'a>(ts: &'a ThemeSet, name: &str) -> Result<&'a Theme, String> {
+    ts.themes.get(name).ok_or_else(|| {
+        let names: Vec<&str> = ts.themes.keys().map(String::as_str).collect();
+        format!(
+            "unknown theme {:?}, expected one of {}",
+            name,
+            names.join(", ")
+        )
+    })
+}
+
+/// Background used behind highlighted code.
+#[derive(Clone, Copy)]
+pub enum CodeBackground {
+    Theme,
+    None,
+    Custom(SyntectColor),
+}
+
+/// How many colors the terminal can show.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColorDepth {
+    TrueColor,
+    /// The xterm 256-color palette.
+    Ansi256,
+    /// The 16 basic colors, whatever the terminal has them set to.
+    Ansi16,
+}
+
+/// Levels of each channel in the 256-color palette's 6×6×6 cube.
+const CUBE_LEVELS: [u8; 6] = [0x00, 0x5F, 0x87, 0xAF, 0xD7, 0xFF];
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+/// The basic color with the same hue, numbered 0 to 15. Since terminals
+/// pick their own shades of these, going by the nearest standard value
+/// would turn most pastel theme colors gray. Colors without much hue are
+/// one of the grays instead.
+fn ansi16(rgb: (u8, u8, u8)) -> u8 {
+    let max = rgb.0.max(rgb.1).max(rgb.2);
+    let min = rgb.0.min(rgb.1).min(rgb.2);
+    if max - min < 0x30 {
+        return match max {
+            0x00..=0x3F => 0,
+            0x40..=0x9F => 8,
+            0xA0..=0xDF => 7,
+            _ => 15,
+        };
+    }
+    let middle = (max as u16 + min as u16) / 2;
+    let on = |channel: u8| (channel as u16 > middle) as u8;
+    let hue = on(rgb.0) | on(rgb.1) << 1 | on(rgb.2) << 2;
+    if max >= 0xE0 {
+        hue + 8
+    } else {
+        hue
+    }
+}
+
+/// The nearest of the 256-color palette's cube and gray ramp colors.
+fn ansi256(rgb: (u8, u8, u8)) -> u8 {
+    let level = |channel: u8| {
+        (0..6)
+            .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - channel as i32).abs())
+            .unwrap()
+    };
+    let (r, g, b) = (level(rgb.0), level(rgb.1), level(rgb.2));
+    let cube = (CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]);
+    let average = (rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3;
+    let gray = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray_level = 8 + 10 * gray;
+    if distance(rgb, (gray_level, gray_level, gray_level)) < distance(rgb, cube) {
+        232 + gray
+    } else {
+        16 + 36 * r as u8 + 6 * g as u8 + b as u8
+    }
+}
+
+/// The escape that sets a foreground or background color, as near as the
+/// terminal can show it.
+fn color_escape(color: SyntectColor, background: bool, depth: ColorDepth) -> String {
+    let rgb = (color.r, color.g, color.b);
+    let layer = if background { 48 } else { 38 };
+    match depth {
+        ColorDepth::TrueColor => format!("\x1B[{};2;{};{};{}m", layer, rgb.0, rgb.1, rgb.2),
+        ColorDepth::Ansi256 => format!("\x1B[{};5;{}m", layer, ansi256(rgb)),
+        ColorDepth::Ansi16 => {
+            let color = ansi16(rgb);
+            let base = if background { 40 } else { 30 };
+            let code = if color < 8 {
+                base + color
+            } else {
+                base + 60 + color - 8
+            };
+            format!("\x1B[{}m", code)
+        }
+    }
+}
+
+/// Escapes highlighted text in its colors, as near as the terminal can show
+/// them, and on its background if `background` is set.
+pub(crate) fn escape_colored(
+    ranges: &[(SyntectStyle, &str)],
+    background: bool,
+    depth: ColorDepth,
+) -> String {
+    let mut output = String::new();
+    for (style, text) in ranges {
+        if background {
+            output.push_str(&color_escape(style.background, true, depth));
+        }
+        output.push_str(&color_escape(style.foreground, false, depth));
+        output.push_str(text);
+    }
+    output
+}
+
+/// How code spans within prose are drawn.
+#[derive(Clone, Copy, PartialEq)]
+pub enum InlineCode {
+    /// One color on the code background, the theme's style for inline code.
+    Tint,
+    /// Fully syntax highlighted, like code blocks.
+    Highlight,
+}
+
+impl CodeBackground {
+    /// Parses `none` or a `#rrggbb` hex color, with or without the `#`.
+    pub fn parse(value: &str) -> Option<CodeBackground> {
+        if value == "none" {
+            return Some(CodeBackground::None);
+        }
+        let hex = value.strip_prefix('#').unwrap_or(value);
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        Some(CodeBackground::Custom(SyntectColor {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+            a: 0xFF,
+        }))
+    }
+}
+
+/// Syntax told apart by weight alone in monochrome output.
+const MONO_SCOPES: &[(&str, FontStyle)] = &[
+    ("keyword, storage, constant.language", FontStyle::BOLD),
+    (
+        "entity.name, support.function, support.macro",
+        FontStyle::UNDERLINE,
+    ),
+    ("comment", FontStyle::ITALIC),
+];
+
+/// A theme with no colors at all, only font styles.
+fn mono_theme() -> Theme {
+    let scopes = MONO_SCOPES
+        .iter()
+        .map(|(selector, font_style)| ThemeItem {
+            scope: selector.parse().unwrap(),
+            style: StyleModifier {
+                foreground: None,
+                background: None,
+                font_style: Some(*font_style),
+            },
+        })
+        .collect();
+    Theme {
+        scopes,
+        ..Theme::default()
+    }
+}
+
+/// Escapes highlighted text using only its font style.
+fn as_font_style_escaped(ranges: &[(SyntectStyle, &str)]) -> String {
+    let mut output = String::new();
+    for (style, text) in ranges {
+        let mut ansi = Style::new();
+        if style.font_style.contains(FontStyle::BOLD) {
+            ansi = ansi.bold();
+        }
+        if style.font_style.contains(FontStyle::ITALIC) {
+            ansi = ansi.italic();
+        }
+        if style.font_style.contains(FontStyle::UNDERLINE) {
+            ansi = ansi.underline();
+        }
+        output.push_str(&ansi.paint(*text));
+    }
+    output
+}
+
+/// An accent color, or bold when color is off.
+fn accent(color: AnsiColor, r: &Renderer) -> Style {
+    if r.options.mono {
+        Style::new().bold()
+    } else {
+        color.on_default()
+    }
+}
+
+fn escape_ranges(ranges: &mut [(SyntectStyle, &str)], r: &Renderer) -> String {
+    let depth = r.options.color_depth;
+    match r.options.code_bg {
+        _ if r.options.mono => as_font_style_escaped(ranges),
+        CodeBackground::Theme => escape_colored(ranges, true, depth),
+        CodeBackground::None => escape_colored(ranges, false, depth),
+        CodeBackground::Custom(color) => {
+            for (style, _) in ranges.iter_mut() {
+                style.background = color;
+            }
+            escape_colored(ranges, true, depth)
+        }
+    }
+}
+
+/// Highlights `text` as escaped terminal output. If syntect comes back with
+/// less text than it was given, the raw text is returned instead so that
+/// nothing goes missing from the output.
+fn highlight(text: &str, r: &Renderer) -> String {
+    let mut h = HighlightLines::new(r.syntax, &r.theme);
+    highlight_with(&mut h, text, r)
+}
+
+fn highlight_with(h: &mut HighlightLines, text: &str, r: &Renderer) -> String {
+    let mut ranges = h.highlight(text, r.ps);
+    escape_or_raw(&mut ranges, text, r)
+}
+
+/// Escapes the ranges syntect highlighted `text` into, or returns `text` as
+/// it is if they don't cover all of it.
+fn escape_or_raw(ranges: &mut [(SyntectStyle, &str)], text: &str, r: &Renderer) -> String {
+    let highlighted_len: usize = ranges.iter().map(|(_, piece)| piece.len()).sum();
+    if highlighted_len < text.len() {
+        return text.to_string();
+    }
+    escape_ranges(ranges, r)
+}
+
+/// Draws inline code in the theme's color for `markup.raw.inline` (its
+/// plain foreground, if it has none) on the code background.
+fn tint(code: &str, r: &Renderer) -> String {
+    let scope = Scope::new("markup.raw.inline").unwrap();
+    let fg = Highlighter::new(&r.theme)
+        .style_for_stack(&[scope])
+        .foreground;
+    let mut out = color_escape(fg, false, r.options.color_depth);
+    let background = match r.options.styles.inline_code_bg {
+        Some(CodeBackground::Custom(bg)) => Some(bg),
+        Some(_) => None,
+        None => code_background(r),
+    };
+    if let Some(bg) = background {
+        out.push_str(&color_escape(bg, true, r.options.color_depth));
+    }
+    out.push_str(code);
+    out
+}
+
+/// Renders spans as terminal text. `context` is the style already in effect
+/// around the spans, which is restored after inline code resets it.
+fn render_spans(spans: &[Span], r: &Renderer, context: Style) -> String {
+    spans
+        .iter()
+        .map(|span| render_span(span, r, context))
+        .collect()
+}
+
+fn render_span(span: &Span, r: &Renderer, context: Style) -> String {
+    match span {
+        Span::Break => "\n".to_string(),
+        Span::Text(text) => text.clone(),
+        Span::Code(code) => {
+            let styled = match r.options.inline_code {
+                InlineCode::Tint if !r.options.mono => tint(code, r),
+                _ => highlight(code, r),
+            };
+            format!("{}{}{}", styled, ANSI_RESET, context.render())
+        }
+        Span::Emphasis(spans) => {
+            let style = match r.options.styles.emphasis {
+                Some(style) => styles::overlay(context, styles::fit(style, r)),
+                None => context.italic(),
+            };
+            render_nested(spans, r, style, context)
+        }
+        Span::Strong(spans) => {
+            let style = match r.options.styles.strong {
+                Some(style) => styles::overlay(context, styles::fit(style, r)),
+                None => context.bold(),
+            };
+            render_nested(spans, r, style, context)
+        }
+        Span::Link(spans, url, _) => link(&render_spans(spans, r, context), url, r),
+        Span::Image(alt, url, _) => link(alt, url, r),
+    }
+}
+
+/// Renders spans drawn in `style` on top of the enclosing `context`. Escape
+/// codes can't turn one attribute off on its own, so ending the spans resets
+/// everything and then puts the enclosing style back, however deeply they're
+/// nested.
+fn render_nested(spans: &[Span], r: &Renderer, style: Style, context: Style) -> String {
+    format!(
+        "{}{}{}{}",
+        style.render(),
+        render_spans(spans, r, style),
+        ANSI_RESET,
+        context.render()
+    )
+}
+
+/// A link as an OSC 8 hyperlink, or as its text followed by its URL on
+/// terminals that may not support them.
+fn link(text: &str, url: &str, r: &Renderer) -> String {
+    if r.options.hyperlinks {
+        format!("\x1B]8;;{}\x1B\\{}\x1B]8;;\x1B\\", url, text)
+    } else if text == url {
+        url.to_string()
+    } else {
+        format!("{} ({})", text, url)
+    }
+}
+
+/// Renders and wraps a paragraph's spans, all drawn in `style`.
+fn wrap_spans(spans: &[Span], r: &Renderer, style: Style) -> String {
+    let out = render_spans(spans, r, style);
+    // textwrap measures lines by their display width, skipping over the
+    // escape sequences that highlighting and hyperlinks add. Breaking lines
+    // anywhere but spaces could cut a URL in two, or cut a hyperlink's URL
+    // off from its escape sequence.
+    let splitter = if r.options.hyphenate {
+        WordSplitter::Custom(hyphenation_points)
+    } else {
+        WordSplitter::NoHyphenation
+    };
+    let wrap = textwrap::Options::new(r.wrap_width)
+        .wrap_algorithm(WrapAlgorithm::FirstFit)
+        .word_separator(WordSeparator::AsciiSpace)
+        .word_splitter(splitter);
+    // Lines are styled one by one, since whatever they get indented by later,
+    // like a quote's bar, resets the style at the start of each. Hard breaks
+    // come through as line breaks of their own.
+    textwrap::wrap(&out, wrap)
+        .iter()
+        .map(|line| style.paint(line.as_ref()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Where a word can be hyphenated. Only plain words are, never ones with
+/// escape sequences, URLs, or identifiers in them, which a hyphen would
+/// break.
+fn hyphenation_points(word: &str) -> Vec<usize> {
+    static DICTIONARY: OnceLock<Standard> = OnceLock::new();
+    let letters = word.trim_end_matches(|c: char| c.is_ascii_punctuation());
+    if letters.is_empty() || !letters.chars().all(char::is_alphabetic) {
+        return Vec::new();
+    }
+    let dictionary = DICTIONARY
+        .get_or_init(|| Standard::from_embedded(Language::EnglishUS).expect("embedded dictionary"));
+    dictionary.hyphenate(letters).breaks
+}
+
+fn map_block(block: Block, r: &Renderer, follows_help: bool) -> Block {
+    let block = style_block(block, r, follows_help);
+    match r.post_process {
+        Some(post_process) => post_process(block),
+        None => block,
+    }
+}
+
+/// The style for each header level, from `#` down. Deeper levels look like
+/// the last one.
+const HEADER_STYLES: &[(AnsiColor, bool)] = &[
+    (AnsiColor::Magenta, true),
+    (AnsiColor::Cyan, false),
+    (AnsiColor::Blue, false),
+];
+
+/// Renders a header as styled text instead of with `#` marks. With
+/// `header_rules`, the top two levels are underlined with a rule.
+fn render_header(spans: Vec<Span>, level: usize, r: &Renderer) -> String {
+    let (color, underline) = HEADER_STYLES[(level.max(1) - 1).min(HEADER_STYLES.len() - 1)];
+    let mut style = accent(color, r).bold();
+    if underline {
+        style = style.underline();
+    }
+    if level > HEADER_STYLES.len() {
+        style = style.italic();
+    }
+    let custom = r.options.styles.headers[level.clamp(1, 6) - 1];
+    if let Some(custom) = custom {
+        style = styles::fit(custom, r);
+    }
+    // The rule takes the header's color, without its font style
+    let rule_style = match custom {
+        Some(_) => Style::new().fg_color(style.get_fg_color()),
+        None => accent(color, r),
+    };
+    let text = wrap_spans(&spans, r, style);
+    let rule = match level {
+        1 if r.options.header_rules => r.glyphs().double_rule,
+        2 if r.options.header_rules => r.glyphs().rule,
+        _ => return text,
+    };
+    let width = text.lines().map(textwrap::core::display_width).max();
+    let rule = rule.repeat(width.unwrap_or_default());
+    format!("{}\n{}", text, rule_style.paint(rule))
+}
+
+/// Whether a paragraph is a compiler-style "help: ..." suggestion.
+fn is_help(spans: &[Span]) -> bool {
+    matches!(spans.first(), Some(Span::Text(text)) if text.trim_start().starts_with("help:"))
+}
+
+fn style_block(block: Block, r: &Renderer, follows_help: bool) -> Block {
+    match block {
+        Block::Header(spans, level) => Block::Raw(render_header(spans, level, r)),
+        Block::Paragraph(spans) if is_help(&spans) => {
+            let text = wrap_spans(&spans, r, accent(AnsiColor::Green, r));
+            Block::Paragraph(vec![Span::Text(text)])
+        }
+        Block::Paragraph(spans) => {
+            Block::Paragraph(vec![Span::Text(wrap_spans(&spans, r, Style::new()))])
+        }
+        Block::Blockquote(blocks) => {
+            // A bar down the quote's left edge, then padding up to the indent
+            let width = r.options.indent.max(1);
+            let bar = match r.options.styles.blockquote_bar {
+                Some(style) => styles::fit(style, r).paint(r.glyphs().bar),
+                None => accent(AnsiColor::Cyan, r).paint(r.glyphs().bar),
+            };
+            let marker = format!("{}{}", bar, " ".repeat(width - 1));
+            let body = render_blocks(blocks, &r.narrowed(width));
+            Block::Raw(indent_lines(&body, &marker, &marker))
+        }
+        Block::Table(table) => Block::Raw(table::render_table(&table, r)),
+        Block::CodeBlock(lang, code) => {
+            let mut labels = Vec::new();
+            if follows_help {
+                let rule = r.glyphs().rule.repeat(2);
+                let label = accent(AnsiColor::Green, r)
+                    .bold()
+                    .paint(format!("{} Suggested fix {}", rule, rule));
+                labels.push(label);
+            }
+            if let Some(badge) = fence_badge(lang.as_deref(), r) {
+                // Set the badge apart from the paragraph before, unless it's
+                // under the help label with the code
+                if labels.is_empty() {
+                    labels.push(String::new());
+                }
+                labels.push(badge);
+            }
+            let mut code = highlight_code(lang.as_deref(), &code, r);
+            if r.options.frame_code {
+                code = format!(
+                    "\n{}",
+                    frame_code(&code, fence_language(lang.as_deref()), r)
+                );
+            }
+            labels.push(code);
+            Block::Raw(labels.join("\n"))
+        }
+        Block::Hr => {
+            let rule = r.glyphs().rule.repeat(r.wrap_width);
+            Block::Raw(Style::new().dimmed().paint(rule))
+        }
+        Block::List(list) => {
+            let markers = match list.start {
+                Some(start) => (start..start + list.items.len())
+                    .map(|n| format!("{}.", n))
+                    .collect(),
+                None => {
+                    let bullets = r.glyphs().bullets;
+                    let bullet = bullets[r.list_depth % bullets.len()];
+                    vec![bullet.to_string(); list.items.len()]
+                }
+            };
+            Block::Raw(render_list(list, markers, r))
+        }
+        Block::Raw(_) => block,
+    }
+}
+
+/// The text of a block as it's printed. Styled blocks are already text, but
+/// `post_process` can hand back anything, so anything else is styled again.
+fn block_text(block: Block, r: &Renderer) -> String {
+    match block {
+        Block::Raw(text) => text,
+        Block::Paragraph(spans) => render_spans(&spans, r, Style::new()),
+        block => block_text(style_block(block, r, false), r),
+    }
+}
+
+/// Maps and serializes a sequence of blocks, as used for the whole document
+/// and for the contents of blockquotes and list items.
+fn render_blocks(blocks: Vec<Block>, r: &Renderer) -> String {
+    let spacing = r.options.spacing;
+    // Within a list item, a nested list hangs right under the item's text.
+    let tight: Vec<bool> = blocks
+        .iter()
+        .map(|block| r.list_depth > 0 && matches!(block, Block::List(_)))
+        .collect();
+    let headers: Vec<bool> = blocks
+        .iter()
+        .map(|block| matches!(block, Block::Header(..)))
+        .collect();
+    let mut output = String::new();
+    let mut follows_code = false;
+    for (i, (is_code, rendered)) in render_each(blocks, r).into_iter().enumerate() {
+        if i > 0 {
+            let blank_lines = if tight[i] {
+                0
+            } else if headers[i] {
+                spacing.headers
+            } else if is_code || follows_code {
+                spacing.code
+            } else {
+                spacing.blocks
+            };
+            // Highlighted code starts with an extra line in its background
+            // color, which stands in for the first blank line before it.
+            let blank_lines = if is_code {
+                blank_lines.saturating_sub(1)
+            } else {
+                blank_lines
+            };
+            output.push_str(&"\n".repeat(blank_lines + 1));
+        }
+        output.push_str(&rendered);
+        follows_code = is_code;
+    }
+    output
+}
+
+/// Maps and serializes each block on its own, noting which were code.
+fn render_each(blocks: Vec<Block>, r: &Renderer) -> Vec<(bool, String)> {
+    let mut follows_help = false;
+    let mut changes = if r.options.diff_examples {
+        example_diff::changes(&blocks)
+    } else {
+        Vec::new()
+    };
+    changes.resize(blocks.len(), Vec::new());
+    blocks
+        .into_iter()
+        .zip(changes)
+        .map(|(block, line_changes)| {
+            let is_code = matches!(block, Block::CodeBlock(..));
+            let help = matches!(&block, Block::Paragraph(spans) if is_help(spans));
+            let mapped = if line_changes.is_empty() {
+                map_block(block, r, follows_help)
+            } else {
+                let r = Renderer {
+                    line_changes,
+                    ..r.clone()
+                };
+                map_block(block, &r, follows_help)
+            };
+            follows_help = help;
+            (is_code, block_text(mapped, r))
+        })
+        .collect()
+}
+
+/// Lays a document out in rows, with each run of prose on the left and the
+/// code examples that follow it on the right. Headers span both columns.
+/// Widths too narrow to split get a single column.
+fn render_columns(blocks: Vec<Block>, r: &Renderer, width: usize) -> String {
+    let gutter = Style::new().dimmed().paint(format!(" {} ", r.glyphs().bar));
+    let left_width = width.saturating_sub(3) / 2;
+    if left_width < MIN_WRAP_WIDTH {
+        return render_blocks(blocks, r);
+    }
+    let left = Renderer {
+        wrap_width: left_width,
+        ..r.clone()
+    };
+
+    let mut rows: Vec<String> = Vec::new();
+    let mut prose: Vec<Block> = Vec::new();
+    let mut code: Vec<Block> = Vec::new();
+    let flush = |prose: &mut Vec<Block>, code: &mut Vec<Block>, rows: &mut Vec<String>| {
+        if prose.is_empty() && code.is_empty() {
+            return;
+        }
+        let prose_text = render_blocks(std::mem::take(prose), &left);
+        let code_text = render_blocks(std::mem::take(code), r);
+        let (prose_lines, code_lines): (Vec<_>, Vec<_>) =
+            (prose_text.lines().collect(), code_text.lines().collect());
+        let height = prose_lines.len().max(code_lines.len());
+        let row: Vec<String> = (0..height)
+            .map(|i| {
+                let prose_line = prose_lines.get(i).copied().unwrap_or_default();
+                let padding = left_width.saturating_sub(textwrap::core::display_width(prose_line));
+                let code_line = code_lines.get(i).copied().unwrap_or_default();
+                format!(
+                    "{}{}{}{}",
+                    prose_line,
+                    " ".repeat(padding),
+                    gutter,
+                    code_line
+                )
+            })
+            .collect();
+        rows.push(row.join("\n"));
+    };
+
+    for block in blocks {
+        match block {
+            Block::Header(..) => {
+                flush(&mut prose, &mut code, &mut rows);
+                rows.push(render_blocks(vec![block], r));
+            }
+            Block::CodeBlock(..) => code.push(block),
+            _ => {
+                if !code.is_empty() {
+                    flush(&mut prose, &mut code, &mut rows);
+                }
+                prose.push(block);
+            }
+        }
+    }
+    flush(&mut prose, &mut code, &mut rows);
+    rows.join("\n\n")
+}
+
+/// Prefixes the first line of `text` with `first` and every following line
+/// with `rest`.
+fn indent_lines(text: &str, first: &str, rest: &str) -> String {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let prefix = if i == 0 { first } else { rest };
+            if line.is_empty() {
+                prefix.trim_end().to_string()
+            } else {
+                format!("{}{}", prefix, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Renders list items with their markers, indenting item contents (and so
+/// any nested lists) by the renderer's indent width. Markers are right
+/// aligned, so items' text all starts in the same column. Items of a loose
+/// list are set apart by blank lines.
+fn render_list(list: List, markers: Vec<String>, r: &Renderer) -> String {
+    let marker_width = markers.iter().map(|m| m.width()).max();
+    let marker_width = marker_width.unwrap_or_default();
+    let width = r.options.indent.max(marker_width + 1);
+    let r = &Renderer {
+        list_depth: r.list_depth + 1,
+        ..r.narrowed(width)
+    };
+    list.items
+        .into_iter()
+        .zip(markers)
+        .map(|(item, marker)| {
+            let body = match item {
+                ListItem::Simple(spans) => wrap_spans(&spans, r, Style::new()),
+                ListItem::Paragraph(blocks) => render_blocks(blocks, r),
+            };
+            let marker = format!(
+                "{}{}{}",
+                " ".repeat(marker_width - marker.width()),
+                marker,
+                " ".repeat(width - marker_width)
+            );
+            indent_lines(&body, &marker, &" ".repeat(width))
+        })
+        .collect::<Vec<_>>()
+        .join(if list.loose { "\n\n" } else { "\n" })
+}
+
+/// Joins runs of adjacent code blocks that share a language into a single
+/// block, so one example split across several fences reads as one snippet.
+fn merge_code_blocks(blocks: Vec<Block>) -> Vec<Block> {
+    let mut merged: Vec<Block> = Vec::with_capacity(blocks.len());
+    for block in blocks {
+        if let (Some(Block::CodeBlock(prev_lang, prev_code)), Block::CodeBlock(lang, code)) =
+            (merged.last_mut(), &block)
+        {
+            if prev_lang == lang {
+                let mut joined = prev_code.trim_end_matches('\n').to_string();
+                joined.push('\n');
+                joined.push_str(code);
+                *prev_code = joined;
+                continue;
+            }
+        }
+        merged.push(block);
+    }
+    merged
+}
+
+/// How rustdoc shows a line of a Rust example: lines starting with `# `, or
+/// just `#`, are hidden boilerplate, and `##` is an escaped literal `#`.
+/// Returns the line with that markup removed, and whether it's hidden.
+fn doc_test_line(line: &str) -> (Cow<'_, str>, bool) {
+    let trimmed = line.trim_start();
+    let indent = &line[..line.len() - trimmed.len()];
+    if let Some(rest) = trimmed.strip_prefix("##") {
+        return (Cow::Owned(format!("{}#{}", indent, rest)), false);
+    }
+    match trimmed.strip_prefix('#') {
+        Some(rest) if rest.is_empty() || rest.starts_with([' ', '\t', '\n']) => {
+            let rest = rest.strip_prefix(' ').unwrap_or(rest);
+            (Cow::Owned(format!("{}{}", indent, rest)), true)
+        }
+        _ => (Cow::Borrowed(line), false),
+    }
+}
+
+/// Whether a word from a fence's info string is one of rustdoc's test
+/// attributes, like `compile_fail` or `E0502`, rather than a language.
+fn is_rustdoc_attribute(word: &str) -> bool {
+    let error_code =
+        word.len() == 5 && word.starts_with('E') && word[1..].chars().all(|c| c.is_ascii_digit());
+    error_code
+        || word.starts_with("edition")
+        || word.starts_with("ignore")
+        || matches!(
+            word,
+            "compile_fail" | "should_panic" | "no_run" | "test_harness" | "allow_fail"
+        )
+}
+
+/// The language of a code block from its fence's info string. Fences with
+/// no language, or with only rustdoc attributes, are Rust, just as rustdoc
+/// treats them.
+fn fence_language(info: Option<&str>) -> &str {
+    info.into_iter()
+        .flat_map(|info| info.split(|c: char| c == ',' || c.is_whitespace()))
+        .find(|word| !word.is_empty() && !is_rustdoc_attribute(word))
+        .unwrap_or("rust")
+}
+
+/// A badge saying what a Rust example's fence says about it, like whether
+/// it compiles. Blocks without an info string don't get one, since rustc
+/// strips them from most explanations and there's no telling.
+fn fence_badge(info: Option<&str>, r: &Renderer) -> Option<String> {
+    let info = info.filter(|info| !info.trim().is_empty())?;
+    if fence_language(Some(info)) != "rust" {
+        return None;
+    }
+    let words: Vec<&str> = info
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|word| !word.is_empty())
+        .collect();
+    let has = |attribute| words.contains(&attribute);
+    let codes: Vec<&str> = words
+        .iter()
+        .copied()
+        .filter(|word| word.starts_with('E') && is_rustdoc_attribute(word))
+        .collect();
+
+    let glyphs = r.glyphs();
+    let (color, mut badge) = if has("compile_fail") {
+        let mut badge = format!("{} fails to compile", glyphs.warning);
+        if !codes.is_empty() {
+            badge.push_str(&format!(": {}", codes.join(", ")));
+        }
+        (AnsiColor::Red, badge)
+    } else if words.iter().any(|word| word.starts_with("ignore")) {
+        (AnsiColor::Yellow, format!("{} not tested", glyphs.untested))
+    } else if has("should_panic") {
+        let badge = format!("{} compiles, panics when run", glyphs.warning);
+        (AnsiColor::Yellow, badge)
+    } else if has("no_run") {
+        (
+            AnsiColor::Green,
+            format!("{} compiles, not run", glyphs.check),
+        )
+    } else {
+        (AnsiColor::Green, format!("{} compiles", glyphs.check))
+    };
+    if let Some(edition) = words.iter().find_map(|word| word.strip_prefix("edition")) {
+        badge.push_str(&format!(" {} edition {}", glyphs.dot, edition));
+    }
+    Some(accent(color, r).paint(badge))
+}
+
+/// Highlights a code block line by line. Every line ends with its own
+/// reset, so styling never carries over a line break and anything added to
+/// the start or end of a line later stays unstyled. `lang` is the block's
+/// info string, if it had one. Languages syntect doesn't know, like `text`
+/// or `console`, are drawn plain.
+fn highlight_code(lang: Option<&str>, code: &str, r: &Renderer) -> String {
+    let language = fence_language(lang);
+    #[cfg(feature = "bat")]
+    if r.options.use_bat && !r.options.mono {
+        if let Some(highlighted) = bat::highlight(code, language) {
+            return format!("\n{}", highlighted);
+        }
+    }
+    let syntax = match language {
+        "rust" => r.syntax,
+        _ => {
+            r.ps.find_syntax_by_token(language)
+                .unwrap_or_else(|| r.ps.find_syntax_plain_text())
+        }
+    };
+    let mut output = String::with_capacity(code.len());
+    let mut h = HighlightLines::new(syntax, &r.theme);
+    let mut input = String::from("\n");
+    let mut hidden = vec![false];
+    let mut marks = vec![None];
+    for (n, line) in LinesWithEndings::from(code).enumerate() {
+        let (line, is_hidden) = match language {
+            "rust" => doc_test_line(line),
+            _ => (Cow::Borrowed(line), false),
+        };
+        if is_hidden && !r.options.show_hidden {
+            continue;
+        }
+        let mark = match r.line_changes.get(n).copied().flatten() {
+            Some(change) => Some(change),
+            None if !is_hidden && is_error_line(&line) => Some(LineMark::Error),
+            None => None,
+        };
+        input.push_str(&line);
+        hidden.push(is_hidden);
+        marks.push(mark);
+    }
+    let background = code_background(r);
+    let width = input.lines().map(UnicodeWidthStr::width).max();
+    let numbers_width = (hidden.len() - 1).to_string().len();
+    // With nothing else to mark them by, marked lines get a marker of their
+    // own in front
+    let markers =
+        !r.options.line_numbers && background.is_none() && marks.iter().any(Option::is_some);
+    // Lines wider than the room left beside the gutter and inside any frame
+    // are soft wrapped
+    let gutter_width = match (r.options.line_numbers, markers) {
+        (true, _) => numbers_width + 3,
+        (false, true) => 2,
+        (false, false) => 0,
+    };
+    let frame_width = if r.options.frame_code { 4 } else { 0 };
+    // Marks the start of each piece of a soft wrapped line after the first
+    let continuation = r.glyphs().continuation;
+    let room = r
+        .wrap_width
+        .saturating_sub(gutter_width + frame_width)
+        .max(MIN_WRAP_WIDTH);
+    let width = width.map(|width| width.min(room));
+    let lines = LinesWithEndings::from(&input).zip(&hidden).zip(&marks);
+    for (i, ((line, &is_hidden), &mark)) in lines.enumerate() {
+        let line_background = match mark {
+            Some(mark) => background.map(|bg| mark.tint(bg)),
+            None => background,
+        };
+        let mut escaped = if is_hidden {
+            // Still feed the line to the highlighter to keep it in step
+            h.highlight(line, r.ps);
+            let text = line.trim_end_matches('\n');
+            let mut dimmed = Style::new().dimmed().paint(text);
+            if let Some(bg) = background {
+                dimmed.insert_str(0, &color_escape(bg, true, r.options.color_depth));
+            }
+            if line.ends_with('\n') {
+                dimmed.push('\n');
+            }
+            dimmed
+        } else if let (Some(_), Some(bg)) = (mark, line_background) {
+            let mut ranges = h.highlight(line, r.ps);
+            for (style, _) in ranges.iter_mut() {
+                style.background = bg;
+            }
+            escape_colored(&ranges, true, r.options.color_depth)
+        } else {
+            highlight_with(&mut h, line, r)
+        };
+        let newline = escaped.ends_with('\n');
+        if newline {
+            escaped.pop();
+        }
+        let pieces = if line.trim_end_matches('\n').width() > room {
+            split_escaped(&escaped, room, room - continuation.width())
+        } else {
+            vec![escaped]
+        };
+        for (k, piece) in pieces.iter().enumerate() {
+            if k > 0 {
+                output.push('\n');
+            }
+            if r.options.line_numbers {
+                output.push_str(&line_number_gutter(i, numbers_width, mark, k > 0, r));
+            } else if markers {
+                let marker = match mark {
+                    Some(mark) if k == 0 => {
+                        accent(mark.color(), r).paint(format!("{} ", mark.symbol(r.glyphs())))
+                    }
+                    _ => "  ".to_string(),
+                };
+                output.push_str(&marker);
+            }
+            let mut piece_width = textwrap::core::display_width(piece);
+            if k > 0 {
+                if let Some(bg) = line_background {
+                    output.push_str(&color_escape(bg, true, r.options.color_depth));
+                }
+                output.push_str(&Style::new().dimmed().paint(continuation));
+                piece_width += continuation.width();
+            }
+            output.push_str(piece);
+            let padding = line_background.filter(|_| r.options.pad_code);
+            if let (Some(bg), Some(width)) = (padding, width) {
+                let missing = width.saturating_sub(piece_width);
+                output.push_str(&color_escape(bg, true, r.options.color_depth));
+                output.push_str(&" ".repeat(missing));
+            }
+            output.push_str(ANSI_RESET);
+        }
+        if newline {
+            output.push('\n');
+        }
+    }
+    output
+}
+
+/// Draws a rounded frame around highlighted code, with its language in the
+/// top border. The frame spans the wrap width, or the widest line if that's
+/// wider. The blank line highlighted code starts with is left out.
+fn frame_code(code: &str, language: &str, r: &Renderer) -> String {
+    let lines: Vec<&str> = code.lines().skip(1).collect();
+    let glyphs = r.glyphs();
+    let [top_left, top_right, bottom_left, bottom_right] = glyphs.frame_corners;
+    let title = format!("{} {} ", glyphs.rule, language);
+    let widest = lines.iter().map(|line| textwrap::core::display_width(line));
+    let inner = widest
+        .max()
+        .unwrap_or_default()
+        .max(r.wrap_width.saturating_sub(4))
+        .max(title.width());
+    let border = Style::new().dimmed();
+    let top = format!(
+        "{}{}{}{}",
+        top_left,
+        title,
+        glyphs.rule.repeat(inner + 2 - title.width()),
+        top_right
+    );
+    let mut framed = vec![border.paint(top)];
+    for line in lines {
+        let padding = inner - textwrap::core::display_width(line);
+        framed.push(format!(
+            "{} {}{} {}",
+            border.paint(glyphs.bar),
+            line,
+            " ".repeat(padding),
+            border.paint(glyphs.bar)
+        ));
+    }
+    let bottom = format!(
+        "{}{}{}",
+        bottom_left,
+        glyphs.rule.repeat(inner + 2),
+        bottom_right
+    );
+    framed.push(border.paint(bottom));
+    framed.join("\n")
+}
+
+/// Splits a line of escaped text into pieces `first` columns wide and then
+/// `rest` columns wide. Each piece ends in a reset, and the next starts by
+/// repeating the escape codes in effect where it was cut, so every piece is
+/// styled on its own.
+fn split_escaped(line: &str, first: usize, rest: usize) -> Vec<String> {
+    let mut pieces = vec![String::new()];
+    let mut active = String::new();
+    let mut column = 0;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1B' {
+            let mut escape = String::from(c);
+            for c in chars.by_ref() {
+                escape.push(c);
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            if escape == ANSI_RESET {
+                active.clear();
+            } else {
+                active.push_str(&escape);
+            }
+            pieces.last_mut().unwrap().push_str(&escape);
+            continue;
+        }
+        let limit = if pieces.len() == 1 { first } else { rest };
+        let width = c.width().unwrap_or(0);
+        if column + width > limit && column > 0 {
+            pieces.last_mut().unwrap().push_str(ANSI_RESET);
+            pieces.push(active.clone());
+            column = 0;
+        }
+        pieces.last_mut().unwrap().push(c);
+        column += width;
+    }
+    pieces
+}
+
+/// The dimmed line number and bar in front of the `i`th line of a code
+/// block, or a colored number and the mark's symbol on marked lines. Line 0
+/// is the blank line blocks start with, which gets no number, and the rest of
+/// a soft wrapped line only gets the bar.
+fn line_number_gutter(
+    i: usize,
+    width: usize,
+    mark: Option<LineMark>,
+    continued: bool,
+    r: &Renderer,
+) -> String {
+    if i == 0 {
+        return " ".repeat(width + 3);
+    }
+    if continued {
+        let gutter = format!("{} {} ", " ".repeat(width), r.glyphs().bar);
+        return Style::new().dimmed().paint(gutter);
+    }
+    if let Some(mark) = mark {
+        let gutter = format!("{:>width$} {} ", i, mark.symbol(r.glyphs()), width = width);
+        return accent(mark.color(), r).paint(gutter);
+    }
+    let gutter = format!("{:>width$} {} ", i, r.glyphs().bar, width = width);
+    Style::new().dimmed().paint(gutter)
+}
+
+/// Whether a line of an example is marked as the one with the error, by a
+/// comment like `// error!` or `// error: cannot borrow ...`.
+fn is_error_line(line: &str) -> bool {
+    line.match_indices("//").any(|(i, _)| {
+        let comment = line[i + 2..].trim_start().to_lowercase();
+        comment
+            .strip_prefix("error")
+            .is_some_and(|rest| rest.starts_with([':', '!', '[']))
+    })
+}
+
+/// Why a line of a code block stands out from the rest.
+#[derive(Clone, Copy, PartialEq)]
+enum LineMark {
+    /// The line an example's error comment points at.
+    Error,
+    /// Only in the example before a fix.
+    Removed,
+    /// Only in the example after a fix.
+    Added,
+}
+
+impl LineMark {
+    fn symbol(self, glyphs: &Glyphs) -> &'static str {
+        match self {
+            LineMark::Error => glyphs.pointer,
+            LineMark::Removed => "-",
+            LineMark::Added => "+",
+        }
+    }
+
+    fn color(self) -> AnsiColor {
+        match self {
+            LineMark::Error | LineMark::Removed => AnsiColor::Red,
+            LineMark::Added => AnsiColor::Green,
+        }
+    }
+
+    /// The code background tinted toward the mark's color, to draw marked
+    /// lines on.
+    fn tint(self, bg: SyntectColor) -> SyntectColor {
+        let (r, g, b) = match self {
+            LineMark::Error | LineMark::Removed => (0xFF, 0x20, 0x20),
+            LineMark::Added => (0x20, 0xC0, 0x20),
+        };
+        let mix = |channel: u8, toward: u8| ((channel as u16 * 3 + toward as u16) / 4) as u8;
+        SyntectColor {
+            r: mix(bg.r, r),
+            g: mix(bg.g, g),
+            b: mix(bg.b, b),
+            a: bg.a,
+        }
+    }
+}
+
+/// The background color code is drawn on, if any.
+fn code_background(r: &Renderer) -> Option<SyntectColor> {
+    match r.options.code_bg {
+        _ if r.options.mono => None,
+        CodeBackground::Theme => r.theme.settings.background,
+        CodeBackground::None => None,
+        CodeBackground::Custom(color) => Some(color),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sets() -> (SyntaxSet, ThemeSet) {
+        (
+            SyntaxSet::load_defaults_newlines(),
+            ThemeSet::load_defaults(),
+        )
+    }
+
+    /// Rendered text with its escape sequences taken out.
+    fn plain(text: &str) -> String {
+        Regex::new(r"\x1B\[[0-9;]*m")
+            .unwrap()
+            .replace_all(text, "")
+            .into_owned()
+    }
+
+    /// The spans of a one paragraph document.
+    fn paragraph(markdown: &str) -> Vec<Span> {
+        match ast::parse(markdown).remove(0) {
+            Block::Paragraph(spans) => spans,
+            block => panic!("not a paragraph: {:?}", block),
+        }
+    }
+
+    fn code_block(lang: &str, code: &str) -> Block {
+        Block::CodeBlock(Some(lang.to_string()), code.to_string())
+    }
+
+    #[test]
+    fn merges_adjacent_code_blocks_of_one_language() {
+        let blocks = ast::parse("```rust\nlet a = 1;\n```\n\n```rust\nlet b = 2;\n```\n");
+        assert_eq!(
+            merge_code_blocks(blocks),
+            vec![code_block("rust", "let a = 1;\nlet b = 2;")]
+        );
+    }
+
+    #[test]
+    fn keeps_code_blocks_of_other_languages_apart() {
+        let blocks = ast::parse("```rust\nlet a = 1;\n```\n\n```text\noutput\n```\n");
+        assert_eq!(
+            merge_code_blocks(blocks),
+            vec![
+                code_block("rust", "let a = 1;"),
+                code_block("text", "output")
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_code_blocks_with_prose_between_apart() {
+        let blocks = ast::parse("```rust\nlet a = 1;\n```\n\nThen:\n\n```rust\nlet b = 2;\n```\n");
+        assert_eq!(merge_code_blocks(blocks.clone()), blocks);
+        assert_eq!(blocks.len(), 3);
+    }
+
+    #[test]
+    fn falls_back_to_raw_text_when_highlighting_comes_up_short() {
+        let (ps, ts) = sets();
+        let options = RenderOptions::default();
+        let r = Renderer::new(&ps, &ts, &options, None);
+        let text = "let x = 1;\n";
+        assert_eq!(escape_or_raw(&mut [], text, &r), text);
+        let mut truncated = [(SyntectStyle::default(), "let x")];
+        assert_eq!(escape_or_raw(&mut truncated, text, &r), text);
+        let mut h = HighlightLines::new(r.syntax, &r.theme);
+        assert_eq!(plain(&highlight_with(&mut h, text, &r)), text);
+    }
+
+    #[test]
+    fn ends_every_line_of_code_with_a_reset() {
+        let (ps, ts) = sets();
+        let options = RenderOptions::default();
+        let r = Renderer::new(&ps, &ts, &options, None);
+        let code = "fn main() {\n    let s = \"a string\";\n    // a comment\n}\n";
+        let highlighted = highlight_code(Some("rust"), code, &r);
+        assert_eq!(highlighted.lines().count(), 5);
+        for line in highlighted.lines() {
+            assert!(line.ends_with(ANSI_RESET), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn resumes_the_surrounding_style_after_inline_code() {
+        let (ps, ts) = sets();
+        let options = RenderOptions::default();
+        let r = Renderer::new(&ps, &ts, &options, None);
+        let bold = Style::new().bold();
+        let bold_italic = bold.italic();
+
+        let rendered = render_spans(&paragraph("**a `b` c**"), &r, Style::new());
+        let resumed = format!("{}{} c", ANSI_RESET, bold.render());
+        assert!(rendered.contains(&resumed), "{:?}", rendered);
+
+        let rendered = render_spans(&paragraph("**a *`b` c* d**"), &r, Style::new());
+        let resumed = format!("{}{} c", ANSI_RESET, bold_italic.render());
+        assert!(rendered.contains(&resumed), "{:?}", rendered);
+        let resumed = format!("{}{} d", ANSI_RESET, bold.render());
+        assert!(rendered.contains(&resumed), "{:?}", rendered);
+    }
+
+    #[test]
+    fn keeps_backticks_inside_double_backtick_code() {
+        let (ps, ts) = sets();
+        let options = RenderOptions::default();
+        let r = Renderer::new(&ps, &ts, &options, None);
+        let spans = paragraph("Write ``a`b`` or `` `let x = vec![];` `` here.");
+        assert!(spans.contains(&Span::Code("a`b".to_string())));
+        assert!(spans.contains(&Span::Code("`let x = vec![];`".to_string())));
+        let rendered = plain(&wrap_spans(&spans, &r, Style::new()));
+        assert_eq!(rendered, "Write a`b or `let x = vec![];` here.");
+    }
+
+    #[test]
+    fn indents_lists_in_quotes_level_by_level() {
+        let (ps, ts) = sets();
+        let options = RenderOptions {
+            indent: 4,
+            ascii: true,
+            mono: true,
+            ..RenderOptions::default()
+        };
+        let rendered = render(
+            "> quoted\n>\n> - one\n>   - two\n>     - three\n",
+            &ps,
+            &ts,
+            &options,
+            None,
+        );
+        // The quote's bar and padding take 4 columns, then each list 4 more
+        assert_eq!(
+            plain(&rendered),
+            "|   quoted\n\
+             |\n\
+             |   *   one\n\
+             |       -   two\n\
+             |           +   three"
+        );
+    }
+
+    #[test]
+    fn labels_code_after_help_as_a_suggested_fix() {
+        let (ps, ts) = sets();
+        let options = RenderOptions::default();
+        let green = AnsiColor::Green.on_default();
+        let fixture = "help: consider borrowing here\n\n```\nlet y = &x;\n```\n";
+        let rendered = render(fixture, &ps, &ts, &options, None);
+        let help = format!("{}help: consider borrowing here", green.render());
+        assert!(rendered.starts_with(&help), "{:?}", rendered);
+        let lines: Vec<String> = plain(&rendered).lines().map(str::to_string).collect();
+        let label = lines.iter().position(|line| line.contains("Suggested fix"));
+        let code = lines.iter().position(|line| line.contains("let y = &x;"));
+        assert!(label.is_some() && label < code, "{:?}", lines);
+
+        let fixture = "Consider borrowing here.\n\n```\nlet y = &x;\n```\n";
+        let rendered = render(fixture, &ps, &ts, &options, None);
+        assert!(!rendered.contains("Suggested fix"));
+        assert!(!rendered.contains(&green.render().to_string()));
+    }
+
+    #[test]
+    fn shows_c0_controls_in_caret_notation() {
+        assert_eq!(sanitize("a\x00b\x07c\x7Fd"), "a^@b^Gc^?d");
+        assert_eq!(sanitize("tab\there\r\nnext"), "tab\there\nnext");
+    }
+
+    #[test]
+    fn shows_stray_escapes_instead_of_passing_them_through() {
+        let sanitized = sanitize("red \x1B[31mtext\x1B]8;;url\x1B\\");
+        assert!(!sanitized.contains('\x1B'));
+        assert_eq!(sanitized, "red ^[[31mtext^[]8;;url^[\\");
+    }
+
+    #[test]
+    fn drops_c1_controls_and_hidden_formatting() {
+        assert_eq!(sanitize("a\u{9B}31mb\u{85}c"), "a31mbc");
+        assert_eq!(sanitize("x\u{202E}y\u{200B}z\u{FEFF}"), "xyz");
+    }
+
+    #[test]
+    fn keeps_zero_width_joiner_sequences() {
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        assert_eq!(sanitize(family), family);
+    }
+
+    #[test]
+    fn keeps_control_bytes_out_of_rendered_output() {
+        let (ps, ts) = sets();
+        let options = RenderOptions {
+            mono: true,
+            ..RenderOptions::default()
+        };
+        let rendered = render("Ring\x07 and \x1B[2Jclear", &ps, &ts, &options, None);
+        assert!(!rendered.contains('\x07'));
+        assert!(!rendered.contains("\x1B[2J"));
+    }
+
+    #[test]
+    fn pads_code_lines_to_one_width() {
+        let (ps, ts) = sets();
+        let options = RenderOptions {
+            pad_code: true,
+            ..RenderOptions::default()
+        };
+        let r = Renderer::new(&ps, &ts, &options, None);
+        let code = "let s = \"\u{4F60}\u{597D}\";\nfn f() {}\n\nlet a_longer_line = 1;\n";
+        let highlighted = highlight_code(Some("rust"), code, &r);
+        let widths: Vec<usize> = highlighted
+            .lines()
+            .map(|line| UnicodeWidthStr::width(plain(line).as_str()))
+            .collect();
+        assert_eq!(widths, vec!["let a_longer_line = 1;".len(); 5]);
+    }
+
+    /// An explanation with an introduction and ten sections of three
+    /// paragraphs each.
+    fn long_explanation() -> String {
+        let mut text = String::from("An introduction.\n\nMore introduction.\n");
+        for section in 1..=10 {
+            text.push_str(&format!("\n## Section {}\n", section));
+            for paragraph in 1..=3 {
+                text.push_str(&format!("\nParagraph {}.{}.\n", section, paragraph));
+            }
+        }
+        text
+    }
+
+    #[test]
+    fn truncates_long_explanations_at_a_section_boundary() {
+        let mut blocks = ast::parse(&long_explanation());
+        assert_eq!(blocks.len(), 42);
+        // Block 12 is the middle of section 3, so all of it goes
+        assert_eq!(truncate_blocks(&mut blocks, 12), 8);
+        assert_eq!(blocks.len(), 10);
+        assert_eq!(blocks.last(), ast::parse("Paragraph 2.3.").last());
+
+        let (ps, ts) = sets();
+        let options = RenderOptions {
+            mono: true,
+            max_blocks: Some(12),
+            ..RenderOptions::default()
+        };
+        let rendered = plain(&render(&long_explanation(), &ps, &ts, &options, None));
+        assert!(rendered.contains("Paragraph 2.3."));
+        assert!(!rendered.contains("Section 3"));
+        assert!(rendered.ends_with("8 more sections; use --full or --open to read all."));
+
+        // The contents list only the sections left
+        let options = RenderOptions {
+            toc: true,
+            max_blocks: Some(22),
+            ..options
+        };
+        let rendered = plain(&render(&long_explanation(), &ps, &ts, &options, None));
+        assert!(rendered.starts_with("Contents"));
+        assert!(rendered.contains("#section-5"));
+        assert!(!rendered.contains("Section 6"));
+    }
+
+    #[test]
+    fn leaves_explanations_whole_without_max_blocks() {
+        let (ps, ts) = sets();
+        let options = RenderOptions {
+            mono: true,
+            ..RenderOptions::default()
+        };
+        let rendered = plain(&render(&long_explanation(), &ps, &ts, &options, None));
+        assert!(rendered.ends_with("Paragraph 10.3."));
+        assert!(!rendered.contains("--full"));
+    }
+
+    #[test]
+    fn wraps_list_items_under_their_text() {
+        let (ps, ts) = sets();
+        let options = RenderOptions {
+            width: 30,
+            ascii: true,
+            mono: true,
+            ..RenderOptions::default()
+        };
+        let fixture = "- a simple item that runs well past the width\n\n\
+                       9. a numbered item that also runs past it\n\
+                       10. ten\n";
+        assert_eq!(
+            plain(&render(fixture, &ps, &ts, &options, None)),
+            "* a simple item that runs well\n\
+             \x20 past the width\n\
+             \n\
+             \x209. a numbered item that also\n\
+             \x20   runs past it\n\
+             10. ten"
+        );
+
+        let fixture = "- a paragraph in a loose item, running long\n\n\
+                       \x20 and a second one, running long too\n";
+        assert_eq!(
+            plain(&render(fixture, &ps, &ts, &options, None)),
+            "* a paragraph in a loose item,\n\
+             \x20 running long\n\
+             \n\
+             \x20 and a second one, running\n\
+             \x20 long too"
+        );
+    }
+
+    #[test]
+    fn renders_one_column_when_too_narrow_for_two() {
+        let (ps, ts) = sets();
+        let fixture = "Some prose.\n\n```\nlet x = 1;\n```\n";
+        let single = render(fixture, &ps, &ts, &RenderOptions::default(), None);
+        for width in [0, 2, 3, 40] {
+            let options = RenderOptions {
+                columns: Some(width),
+                ..RenderOptions::default()
+            };
+            assert_eq!(render(fixture, &ps, &ts, &options, None), single);
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! rustc's own lints, like `unused_must_use`, and rustdoc's, like
+//! `rustdoc::broken_intra_doc_links`, explained from what `-W help` says
+//! about them and their documentation in the rustc and rustdoc books, which
+//! the `rust-docs` component installs.
+
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::process::Command;
+
+use crate::{html, index, toolchain};
+
+/// A lint or lint group from `rustc -W help` or `rustdoc -W help`.
+pub struct Lint {
+    /// The name, with underscores, like `unused_must_use` or
+    /// `rustdoc::bare_urls`.
+    pub name: String,
+    /// `allow`, `warn`, `deny` or `forbid`, or `None` for a group.
+    pub level: Option<String>,
+    /// What the lint checks for, or the lints in a group.
+    pub meaning: String,
+}
+
+/// Whether `name` is shaped like a lint name rather than an error code.
+pub fn is_lint_name(name: &str) -> bool {
+    let name = name.strip_prefix("rustdoc::").unwrap_or(name);
+    name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && !(name.len() == 5 && name.starts_with('e') && name[1..].parse::<u16>().is_ok())
+}
+
+/// Every lint and lint group the toolchain's rustc and rustdoc know.
+pub fn listing() -> Vec<Lint> {
+    let mut lints = help_listing(toolchain::rustc());
+    // rustdoc lists rustc's lints as well as its own
+    let rustdoc = help_listing(toolchain::rustdoc());
+    lints.extend(
+        rustdoc
+            .into_iter()
+            .filter(|lint| lint.name.starts_with("rustdoc::")),
+    );
+    lints
+}
+
+/// The lints and lint groups in a compiler's `-W help`.
+fn help_listing(mut command: Command) -> Vec<Lint> {
+    let output = match command.args(["-W", "help"]).output() {
+        Ok(output) if output.status.success() => output,
+        _ => return Vec::new(),
+    };
+    let help = String::from_utf8_lossy(&output.stdout);
+    let mut lints = Vec::new();
+    let mut groups = false;
+    for line in help.lines() {
+        // Checks and groups are listed under headers like "Lint checks
+        // provided by rustc:" and "Lint groups loaded by this crate:"
+        if line.starts_with("Lint checks") {
+            groups = false;
+        } else if line.starts_with("Lint groups") {
+            groups = true;
+        }
+        // Lines are a right-aligned name, then the level for lints, then
+        // the meaning, all separated by runs of spaces
+        let mut fields = line.trim().splitn(2, "  ");
+        let name = fields.next().unwrap_or_default();
+        let rest = fields.next().unwrap_or_default().trim_start();
+        if name.is_empty() || rest.is_empty() || name == "name" || name == "----" {
+            continue;
+        }
+        if !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == ':')
+        {
+            continue;
+        }
+        let (level, meaning) = if groups {
+            (None, rest)
+        } else {
+            match rest.split_once(' ') {
+                Some((level, meaning)) => (Some(level.to_string()), meaning.trim_start()),
+                None => continue,
+            }
+        };
+        lints.push(Lint {
+            name: name.replace('-', "_"),
+            level,
+            meaning: meaning.to_string(),
+        });
+    }
+    lints
+}
+
+/// The lint called `name`. rustdoc's lints can go without their
+/// `rustdoc::`, as they could before they had it.
+fn find(name: &str) -> Option<Lint> {
+    let mut lints = listing();
+    let at = lints
+        .iter()
+        .position(|lint| lint.name == name)
+        .or_else(|| {
+            let rustdoc = format!("rustdoc::{}", name);
+            lints.iter().position(|lint| lint.name == rustdoc)
+        })?;
+    Some(lints.swap_remove(at))
+}
+
+/// Which tool a lint belongs to.
+fn tool(name: &str) -> &'static str {
+    if name.starts_with("rustdoc::") {
+        "rustdoc"
+    } else {
+        "rustc"
+    }
+}
+
+/// The explanation of a lint or lint group, as markdown, or `None` if
+/// rustc has no lint by that name.
+pub fn explain(name: &str) -> Option<String> {
+    let lint = find(name)?;
+    let name = &lint.name;
+    let level = match &lint.level {
+        Some(level) => level,
+        None => {
+            let members: Vec<String> = lint
+                .meaning
+                .split(", ")
+                .map(|member| format!("* `{}`", member.replace('-', "_")))
+                .collect();
+            return Some(format!(
+                "`{}` is a group of {} lints:\n\n{}\n",
+                name,
+                tool(name),
+                members.join("\n")
+            ));
+        }
+    };
+    let summary = format!(
+        "`{}` is a {} lint, `{}` by default.",
+        name,
+        tool(name),
+        level
+    );
+    Some(match documentation(name, level) {
+        Some(docs) => format!("{}\n\n{}", summary, docs),
+        None => format!(
+            "{} It checks for: {}.\n\nThe `rust-docs` component has its full \
+             documentation, with examples: `rustup component add rust-docs`.\n",
+            summary, lint.meaning
+        ),
+    })
+}
+
+/// The lints named most like `name`, nearest first, for a name rustc
+/// doesn't know.
+pub fn suggestions<'a>(lints: &'a [Lint], name: &str) -> Vec<&'a str> {
+    let most = (name.len() / 4).max(2);
+    let mut near: Vec<(usize, &str)> = lints
+        .iter()
+        .map(|lint| (index::edit_distance(name, &lint.name), lint.name.as_str()))
+        .filter(|&(distance, _)| distance <= most)
+        .collect();
+    near.sort();
+    near.into_iter().take(3).map(|(_, name)| name).collect()
+}
+
+/// Where the rustc or rustdoc book documents a lint.
+pub fn docs_url(name: &str) -> String {
+    let lint = find(name);
+    if let Some(lint) = lint.as_ref().filter(|lint| tool(&lint.name) == "rustdoc") {
+        return format!(
+            "https://doc.rust-lang.org/rustdoc/lints.html#{}",
+            lint.name.trim_start_matches("rustdoc::")
+        );
+    }
+    let level = lint
+        .and_then(|lint| lint.level)
+        .unwrap_or_else(|| "warn".to_string());
+    format!(
+        "https://doc.rust-lang.org/rustc/lints/listing/{}.html#{}",
+        listing_page(&level),
+        name.replace('_', "-")
+    )
+}
+
+fn listing_page(level: &str) -> &'static str {
+    match level {
+        "allow" => "allowed-by-default",
+        "deny" | "forbid" => "deny-by-default",
+        _ => "warn-by-default",
+    }
+}
+
+/// The lint each rust-lang/rust issue is tracked by, going by the issues the
+/// lint listing links to, by issue number.
+pub fn issues() -> HashMap<String, String> {
+    let mut issues = HashMap::new();
+    let issue_pattern = Regex::new(r"rust-lang/rust/issues/(\d+)").unwrap();
+    for level in ["allow", "warn", "deny"] {
+        let html = book_page(&listing_path(level)).unwrap_or_default();
+        for section in html.split("<h2 id=\"").skip(1) {
+            let (id, body) = section.split_once('"').unwrap_or_default();
+            for issue in issue_pattern.captures_iter(body) {
+                issues
+                    .entry(issue[1].to_string())
+                    .or_insert_with(|| id.replace('-', "_"));
+            }
+        }
+    }
+    issues
+}
+
+/// Where the rustc book lists the lints at a default level.
+fn listing_path(level: &str) -> String {
+    format!("rustc/lints/listing/{}.html", listing_page(level))
+}
+
+/// A page of the books the `rust-docs` component installs, if it is.
+fn book_page(path: &str) -> Option<String> {
+    let page = toolchain::sysroot()?.join("share/doc/rust/html").join(path);
+    fs::read_to_string(page).ok()
+}
+
+/// A lint's section of the rustc or rustdoc book, as markdown.
+fn documentation(name: &str, level: &str) -> Option<String> {
+    let (path, id) = match name.strip_prefix("rustdoc::") {
+        Some(name) => ("rustdoc/lints.html".to_string(), name.to_string()),
+        None => (listing_path(level), name.replace('_', "-")),
+    };
+    let html = book_page(&path)?;
+    let section = html::section(&html, "h2", &id)?;
+    let page_url = format!("https://doc.rust-lang.org/{}", path);
+    Some(html::to_markdown(section, &page_url))
+}
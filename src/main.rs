@@ -1,214 +1,1672 @@
-use ansi_term::{ANSIStrings, Color, Style};
-use atty;
-use markdown::{generate_markdown, tokenize, Block, ListItem, Span};
+mod bundled;
+mod cli;
+mod completions;
+mod config;
+mod diagnostics;
+mod future_incompat;
+mod html;
+mod index;
+mod lints;
+mod pager;
+mod pipe;
+mod releases;
+mod scan;
+mod serve;
+mod settings;
+mod term;
+mod themes;
+mod toolchain;
+mod watch;
+mod wrapper;
+
+use anstream::AutoStream;
+use anstyle::{AnsiColor, Style};
+use cargo_explain::{
+    diff_themes, diff_words, find_theme, glyphs, related_codes, render, render_json,
+    CodeBackground, ColorDepth, InlineCode, Paint, RenderOptions, Spacing, Styles, ANSI_RESET,
+    DEFAULT_INDENT, DEFAULT_LIGHT_THEME, DEFAULT_THEME, DEFAULT_WIDTH,
+};
+use clap::{CommandFactory, FromArgMatches, ValueEnum};
+use cli::Cli;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::error::Error;
+use std::fs;
+use std::io::{self, Read, Write};
 use std::path::PathBuf;
 use std::process::{self, Command, Stdio};
-use syntect::easy::HighlightLines;
 use syntect::highlighting::ThemeSet;
-use syntect::parsing::{SyntaxReference, SyntaxSet};
-use syntect::util::as_24_bit_terminal_escaped;
-use textwrap;
-
-const SYNTECT_THEME: &str = "base16-eighties.dark";
-const ANSI_RESET: &str = "\x1B[0m";
-
-fn map_span(span: Span, syntax: &SyntaxReference, ps: &SyntaxSet, ts: &ThemeSet) -> Span {
-    match span {
-        Span::Code(code) => {
-            let mut h = HighlightLines::new(syntax, &ts.themes[SYNTECT_THEME]);
-            let ranges = h.highlight(&code, ps);
-            let escaped = as_24_bit_terminal_escaped(&ranges, true);
-            Span::Text(format!("{}{}", escaped, ANSI_RESET))
-        }
-        Span::Emphasis(spans) => {
-            let mut spans = map_spans(spans, syntax, ps, ts);
-            let style = Style::new().italic();
-            spans.insert(0, Span::Text(style.prefix().to_string()));
-            spans.push(Span::Text(style.suffix().to_string()));
-            Span::Emphasis(spans)
-        }
-        Span::Strong(spans) => {
-            let mut spans = map_spans(spans, syntax, ps, ts);
-            let style = Style::new().bold();
-            spans.insert(0, Span::Text(style.prefix().to_string()));
-            spans.push(Span::Text(style.suffix().to_string()));
-            Span::Strong(spans)
-        }
-        _ => span,
-    }
-}
-
-fn map_spans(
-    spans: Vec<Span>,
-    syntax: &SyntaxReference,
-    ps: &SyntaxSet,
-    ts: &ThemeSet,
-) -> Vec<Span> {
-    spans
-        .into_iter()
-        .map(|span| map_span(span, syntax, ps, ts))
-        .collect()
-}
-
-fn wrap_spans(
-    spans: Vec<Span>,
-    syntax: &SyntaxReference,
-    ps: &SyntaxSet,
-    ts: &ThemeSet,
-) -> Vec<Span> {
-    let mapped = map_spans(spans, syntax, ps, ts);
-    let out = generate_markdown(vec![Block::Paragraph(mapped)]);
-    vec![Span::Text(textwrap::fill(&out, 80))]
-}
-
-fn map_block(block: Block, syntax: &SyntaxReference, ps: &SyntaxSet, ts: &ThemeSet) -> Block {
-    match block {
-        Block::Header(spans, level) => Block::Header(map_spans(spans, syntax, ps, ts), level),
-        Block::Paragraph(spans) => Block::Paragraph(wrap_spans(spans, syntax, ps, ts)),
-        Block::Blockquote(blocks) => Block::Blockquote(map_blocks(blocks, syntax, ps, ts)),
-        Block::CodeBlock(_, code) => {
-            Block::Paragraph(vec![Span::Text(highlight_code(&code, syntax, ps, ts))])
-        }
-        Block::OrderedList(items, something) => {
-            let items = items
-                .into_iter()
-                .map(|item| match item {
-                    ListItem::Simple(spans) => ListItem::Simple(map_spans(spans, syntax, ps, ts)),
-                    ListItem::Paragraph(blocks) => {
-                        ListItem::Paragraph(map_blocks(blocks, syntax, ps, ts))
-                    }
-                })
-                .collect();
-            Block::OrderedList(items, something)
-        }
-        Block::UnorderedList(items) => {
-            let items = items
-                .into_iter()
-                .map(|item| match item {
-                    ListItem::Simple(spans) => ListItem::Simple(map_spans(spans, syntax, ps, ts)),
-                    ListItem::Paragraph(blocks) => {
-                        ListItem::Paragraph(map_blocks(blocks, syntax, ps, ts))
-                    }
-                })
-                .collect();
-            Block::UnorderedList(items)
+use syntect::parsing::SyntaxSet;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Lowest `--match` score that's trusted enough to explain.
+const MIN_MATCH_CONFIDENCE: f64 = 0.5;
+/// Largest code range explained without `--force`.
+const MAX_RANGE: usize = 100;
+/// Narrowest terminal that `--columns` will split in two.
+const MIN_COLUMNS_WIDTH: usize = 140;
+/// Explanations longer than this many screens get a table of contents.
+const AUTO_TOC_SCREENS: usize = 3;
+const DOCS_BASE: &str = "https://doc.rust-lang.org/error_codes";
+const CLIPPY_DOCS: &str = "https://rust-lang.github.io/rust-clippy/master/index.html";
+
+struct Options {
+    command_name: String,
+    err_names: Vec<String>,
+    docs_base: String,
+    /// The chosen theme, or none to follow bat's or pick one for the
+    /// terminal's background.
+    theme: Option<String>,
+    copy_url: bool,
+    open: bool,
+    merge_code: bool,
+    index: bool,
+    code_bg: CodeBackground,
+    inline_code: InlineCode,
+    pager: Option<String>,
+    no_pager: bool,
+    force: bool,
+    prompt: Option<bool>,
+    indent: usize,
+    spacing: Spacing,
+    match_message: Option<String>,
+    /// Compiler output to take codes from, or `Some(None)` to read it
+    /// from stdin.
+    from_message: Option<Option<String>>,
+    /// A build log to take errors from, or `Some(None)` to read it from
+    /// stdin.
+    log: Option<Option<PathBuf>>,
+    future_incompat: bool,
+    no_trailing_reset: bool,
+    toc: bool,
+    no_toc: bool,
+    no_related: bool,
+    numbered: bool,
+    group_by: GroupBy,
+    pad_code: bool,
+    explain_all_in: Option<PathBuf>,
+    max_blocks: Option<usize>,
+    full: bool,
+    mono: bool,
+    ascii: bool,
+    styles: Styles,
+    color: ColorWhen,
+    format: Format,
+    json_render: bool,
+    columns: bool,
+    serve: bool,
+    pipe: bool,
+    watch: bool,
+    hyperlinks: Hyperlinks,
+    locale: Option<String>,
+    diff_themes: Option<(String, String)>,
+    /// Two rustup toolchains to compare explanations between
+    diff_toolchains: Option<(String, String)>,
+    trailing_newline: TrailingNewline,
+    pin_title: bool,
+    show_hidden: bool,
+    header_rules: bool,
+    hyphenate: bool,
+    line_numbers: bool,
+    diff_examples: bool,
+    frame_code: bool,
+    width: Option<usize>,
+    #[cfg(feature = "bat")]
+    use_bat: bool,
+}
+
+fn parse_args(args: Vec<String>) -> Options {
+    let (args, command_name) = rewrite_args(args);
+    let matches = Cli::command()
+        .bin_name(&command_name)
+        .get_matches_from(args);
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if let Some(cli::Command::Completions { shell }) = cli.command {
+        // Shells complete the binary cargo runs, not `cargo explain`
+        completions::generate(shell, "cargo-explain");
+        process::exit(0);
+    }
+    if let Some(toolchain) = &cli.toolchain {
+        toolchain::select(toolchain).unwrap_or_else(|e| error(&e));
+    }
+    if let Some(rustc) = &cli.rustc {
+        toolchain::select_binary(rustc).unwrap_or_else(|e| error(&e));
+    }
+    if let Some(release) = &cli.release {
+        toolchain::select_release(release).unwrap_or_else(|e| error(&e));
+    }
+    // The project's rust-toolchain.toml. Without a rustc to ask, the index
+    // bundled at build time is fallen back on once running it fails.
+    if cli.toolchain.is_none() && cli.rustc.is_none() && cli.release.is_none() {
+        toolchain::select_pinned();
+    }
+    if cli.live {
+        toolchain::stay_live();
+    }
+    if cli.version {
+        print!("{}", toolchain::version_report());
+        process::exit(0);
+    }
+    // A rustc command line after it is run before any of this
+    if cli.wrapper.is_some() {
+        error("--wrapper needs a rustc command line to run, like `--wrapper rustc main.rs`");
+    }
+
+    settings::resolve(cli, command_name)
+}
+
+/// The command line as clap should see it, and the name to show for the
+/// command: `cargo explain` when cargo ran it as a subcommand.
+fn rewrite_args(mut args: Vec<String>) -> (Vec<String>, String) {
+    let path = PathBuf::from(&args[0]);
+    let mut command_name = path
+        .file_name()
+        .map(|f| f.to_string_lossy().into_owned())
+        .unwrap();
+    // Was this invoked via cargo-explain instead of directly? Cargo runs
+    // `cargo-explain explain ...`, so only the binary under that name gets
+    // the subcommand stripped, and only the first argument.
+    let via_cargo = path.file_stem().is_some_and(|stem| stem == "cargo-explain");
+    if via_cargo && args.get(1).is_some_and(|arg| arg == "explain") {
+        args.remove(1);
+        command_name = "cargo explain".to_string();
+    }
+    // `+nightly` picks a toolchain, as it does for cargo and rustc
+    if let Some(toolchain) = args.get(1).and_then(|arg| arg.strip_prefix('+')) {
+        args[1] = format!("--toolchain={}", toolchain);
+    }
+    (args, command_name)
+}
+
+/// Built-in presets for a coherent group of rendering options.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Profile {
+    /// Tight indentation, merged code blocks, no code background.
+    Compact,
+    /// A table of contents, padded code blocks, and side-by-side columns.
+    Docs,
+    /// No color, no code background, and wide indentation.
+    Accessible,
+}
+
+impl Profile {
+    fn apply(self, options: &mut Options) {
+        match self {
+            Profile::Compact => {
+                options.indent = 1;
+                options.merge_code = true;
+                options.code_bg = CodeBackground::None;
+            }
+            Profile::Docs => {
+                options.toc = true;
+                options.pad_code = true;
+                options.columns = true;
+            }
+            Profile::Accessible => {
+                options.mono = true;
+                options.code_bg = CodeBackground::None;
+                options.indent = 4;
+            }
         }
-        _ => block,
     }
 }
 
-fn map_blocks(
-    spans: Vec<Block>,
-    syntax: &SyntaxReference,
-    ps: &SyntaxSet,
-    ts: &ThemeSet,
-) -> Vec<Block> {
-    spans
+fn error(message: &str) -> ! {
+    let strings = &[
+        AnsiColor::Red.on_default().bold().paint("error"),
+        Style::default().bold().paint(": "),
+        Style::default().bold().paint(message),
+    ];
+    anstream::eprintln!("{}", strings.concat());
+    process::exit(1);
+}
+
+fn help(message: &str) {
+    let strings = &[
+        AnsiColor::Cyan.on_default().bold().paint("help"),
+        Style::default().bold().paint(": "),
+        Style::default().paint(message),
+    ];
+    anstream::eprintln!("{}", strings.concat());
+}
+
+/// Follows up on codes rustc couldn't explain with the nearest codes it
+/// can, or with a hint when they're past every code this toolchain knows.
+/// Lint names get the nearest lint names.
+fn suggest_codes(codes: &[String]) {
+    let names: Vec<&String> = codes
+        .iter()
+        .filter(|code| lints::is_lint_name(code))
+        .collect();
+    if !names.is_empty() {
+        let listing = lints::listing();
+        for name in &names {
+            if let [nearest @ .., last] = lints::suggestions(&listing, name).as_slice() {
+                let prefix = if names.len() > 1 {
+                    format!("for {}, ", name)
+                } else {
+                    String::new()
+                };
+                let options = match nearest {
+                    [] => last.to_string(),
+                    _ => format!("{} or {}", nearest.join(", "), last),
+                };
+                help(&format!("{}did you mean {}?", prefix, options));
+            }
+        }
+    }
+    let codes: Vec<&String> = codes
+        .iter()
+        .filter(|code| code.starts_with('E') && normalize_code(code).as_ref() == Some(code))
+        .collect();
+    if codes.is_empty() {
+        return;
+    }
+    let last = index::last_code();
+    let several = codes.len() > 1;
+    for code in codes {
+        let prefix = if several {
+            format!("for {}, ", code)
+        } else {
+            String::new()
+        };
+        if let Some(last) = last.as_ref().filter(|last| code > *last) {
+            help(&format!(
+                "{} is past {}, the last code this toolchain knows; it may be new in a later Rust",
+                code, last
+            ));
+            continue;
+        }
+        match index::suggestions(code).as_slice() {
+            [] => {}
+            [only] => help(&format!("{}did you mean {}?", prefix, only)),
+            [rest @ .., last] => help(&format!(
+                "{}did you mean {} or {}?",
+                prefix,
+                rest.join(", "),
+                last
+            )),
+        }
+    }
+}
+
+fn warn(message: &str) {
+    let strings = &[
+        AnsiColor::Yellow.on_default().bold().paint("warning"),
+        Style::default().bold().paint(": "),
+        Style::default().paint(message),
+    ];
+    anstream::eprintln!("{}", strings.concat());
+}
+
+/// Stdout for rendered output. Escapes pass through to terminals that take
+/// them and become console calls on older Windows consoles that don't. They
+/// are left out when output isn't `styled`, or on dumb terminals.
+fn styled_stdout(styled: bool) -> AutoStream<io::Stdout> {
+    if !styled || env::var_os("TERM").is_some_and(|term| term == "dumb") {
+        AutoStream::never(io::stdout())
+    } else {
+        AutoStream::always(io::stdout())
+    }
+}
+
+fn docs_url(docs_base: &str, err_name: &str) -> String {
+    if let Some(lint) = err_name.strip_prefix("clippy::") {
+        return format!("{}#{}", CLIPPY_DOCS, lint);
+    }
+    if lints::is_lint_name(err_name) {
+        return lints::docs_url(err_name);
+    }
+    format!("{}/{}.html", docs_base.trim_end_matches('/'), err_name)
+}
+
+/// Candidate clipboard programs for the current platform, tried in order.
+fn clipboard_commands() -> &'static [&'static [&'static str]] {
+    if cfg!(target_os = "macos") {
+        &[&["pbcopy"]]
+    } else if cfg!(windows) {
+        &[&["clip"]]
+    } else {
+        &[
+            &["wl-copy"],
+            &["xclip", "-selection", "clipboard"],
+            &["xsel", "--clipboard", "--input"],
+        ]
+    }
+}
+
+fn copy_to_clipboard(text: &str) -> Result<(), Box<dyn Error>> {
+    for command in clipboard_commands() {
+        let child = Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(_) => continue,
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        if child.wait()?.success() {
+            return Ok(());
+        }
+    }
+    Err("no clipboard program found".into())
+}
+
+/// Candidate programs for opening a URL in the default browser, tried in
+/// order after `$BROWSER`.
+fn browser_commands() -> &'static [&'static [&'static str]] {
+    if cfg!(target_os = "macos") {
+        &[&["open"]]
+    } else if cfg!(windows) {
+        // `start` treats its first quoted argument as a window title
+        &[&["cmd", "/C", "start", ""]]
+    } else {
+        &[&["xdg-open"], &["gio", "open"], &["wslview"]]
+    }
+}
+
+fn open_in_browser(url: &str) -> Result<(), Box<dyn Error>> {
+    // `$BROWSER` may be a terminal browser, so it keeps the terminal
+    if let Some(browser) = env::var_os("BROWSER").filter(|browser| !browser.is_empty()) {
+        if Command::new(browser)
+            .arg(url)
+            .status()
+            .is_ok_and(|status| status.success())
+        {
+            return Ok(());
+        }
+    }
+    for command in browser_commands() {
+        let status = Command::new(command[0])
+            .args(&command[1..])
+            .arg(url)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        if status.is_ok_and(|status| status.success()) {
+            return Ok(());
+        }
+    }
+    Err("no browser found".into())
+}
+
+fn render_options(options: &Options) -> RenderOptions {
+    let width = options.width.or_else(term::width);
+    RenderOptions {
+        theme: options
+            .theme
+            .clone()
+            .unwrap_or_else(|| DEFAULT_THEME.to_string()),
+        code_bg: options.code_bg,
+        inline_code: options.inline_code,
+        indent: options.indent,
+        spacing: options.spacing,
+        width: width.unwrap_or(DEFAULT_WIDTH),
+        merge_code: options.merge_code,
+        toc: options.toc && !options.no_toc,
+        toc_after_lines: term::height()
+            .map(|lines| lines * AUTO_TOC_SCREENS)
+            .filter(|_| !options.no_toc),
+        numbered: options.numbered,
+        pad_code: options.pad_code,
+        max_blocks: options.max_blocks.filter(|_| !options.full),
+        mono: options.mono,
+        show_hidden: options.show_hidden,
+        header_rules: options.header_rules,
+        hyphenate: options.hyphenate,
+        line_numbers: options.line_numbers,
+        diff_examples: options.diff_examples,
+        frame_code: options.frame_code,
+        columns: if options.columns {
+            width.filter(|&width| width >= MIN_COLUMNS_WIDTH)
+        } else {
+            None
+        },
+        hyperlinks: match options.hyperlinks {
+            // Links have to be spelled out for their URLs to survive
+            _ if options.color == ColorWhen::Never => false,
+            Hyperlinks::Always => true,
+            Hyperlinks::Never => false,
+            Hyperlinks::Auto => atty::is(atty::Stream::Stdout) && term::supports_hyperlinks(),
+        },
+        color_depth: color_depth(options),
+        styles: options.styles,
+        ascii: options.ascii,
+        #[cfg(feature = "bat")]
+        use_bat: options.use_bat,
+    }
+}
+
+/// How many colors to render with. Only colored terminal output needs the
+/// terminal asked.
+fn color_depth(options: &Options) -> ColorDepth {
+    if options.format == Format::Terminal && options.color != ColorWhen::Never && !options.mono {
+        term::color_depth()
+    } else {
+        ColorDepth::TrueColor
+    }
+}
+
+/// `rustc --explain <err_name>`, asking for the explanation in `locale`.
+/// Clippy lints, named like `clippy::needless_return`, are asked of
+/// `cargo clippy --explain` instead.
+fn explain_command(err_name: &str, locale: Option<&str>) -> Command {
+    if let Some(lint) = err_name.strip_prefix("clippy::") {
+        let mut command = toolchain::cargo();
+        command.args(["clippy", "--explain", lint]);
+        return command;
+    }
+    explain_with(toolchain::rustc(), err_name, locale)
+}
+
+/// `rustc --explain <err_name>` for a particular rustc command.
+fn explain_with(mut command: Command, err_name: &str, locale: Option<&str>) -> Command {
+    if let Some(locale) = locale {
+        command.arg(format!("-Ztranslate-lang={}", locale));
+    }
+    command.args(["--explain", err_name]);
+    command
+}
+
+/// Runs `rustc --explain` from a particular toolchain, returning `None` if
+/// that toolchain has nothing to say about the code.
+fn toolchain_explanation(
+    toolchain: &str,
+    err_name: &str,
+    locale: Option<&str>,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let command = toolchain::toolchain_rustc(toolchain);
+    let result = explain_with(command, err_name, locale).output()?;
+    if !result.status.success() {
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8_lossy(&result.stdout).into_owned()))
+}
+
+/// Whether rustc will take `locale` for its translations. Only nightly
+/// toolchains accept `-Z` flags at all.
+fn locale_supported(locale: &str) -> bool {
+    explain_command("E0001", Some(locale))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Runs `rustc --explain`, returning `None` if rustc has nothing to say
+/// about the code. rustc's lints are explained from their documentation.
+fn fetch_explanation(
+    err_name: &str,
+    locale: Option<&str>,
+) -> Result<Option<String>, Box<dyn Error>> {
+    if lints::is_lint_name(err_name) {
+        return Ok(lints::explain(err_name));
+    }
+    if toolchain::without_rustc() {
+        let entry = toolchain::stored_entry(err_name).filter(|entry| entry.has_explanation);
+        return Ok(entry.map(|entry| entry.explanation));
+    }
+    let result = match explain_command(err_name, locale).output() {
+        Ok(result) => result,
+        Err(e) if toolchain::fall_back_to_bundle(&e) => return fetch_explanation(err_name, locale),
+        Err(e) => return Err(e.into()),
+    };
+    if !result.status.success() {
+        return Ok(None);
+    }
+    let input = String::from_utf8(result.stdout)
+        .expect("rustc --explain terminal output wasn't valid utf-8");
+    Ok(Some(input))
+}
+
+/// Brings an error code as it's typed or pasted, like `e0308`, `308` or
+/// `error[E0308]:`, to the `E0308` form rustc takes, and lints like
+/// `Unused-Must-Use` or `clippy::Needless-Return` to `unused_must_use` and
+/// `clippy::needless_return`. Returns `None` for anything that isn't one.
+fn normalize_code(arg: &str) -> Option<String> {
+    let code = arg.trim();
+    if let Some(lint) = code.strip_prefix("clippy::") {
+        return Some(format!("clippy::{}", lint.to_lowercase().replace('-', "_")));
+    }
+    let code = match (code.find('['), code.rfind(']')) {
+        (Some(open), Some(close)) if open < close => &code[open + 1..close],
+        _ => code.trim_end_matches(':'),
+    };
+    let digits = code.strip_prefix(['E', 'e']).unwrap_or(code);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        let lint = code.to_lowercase().replace('-', "_");
+        return Some(lint).filter(|lint| lints::is_lint_name(lint));
+    }
+    // Too long to be a code, but not a lint either
+    if digits.len() > 4 {
+        return None;
+    }
+    Some(format!("E{:0>4}", digits))
+}
+
+fn parse_code_number(code: &str) -> Option<usize> {
+    normalize_code(code)?[1..].parse().ok()
+}
+
+/// Expands an inclusive range like `E0300..E0320` into the codes it covers.
+fn expand_range(range: &str) -> Option<Vec<String>> {
+    let (start, end) = range.split_once("..")?;
+    let end = end.strip_prefix('=').unwrap_or(end);
+    let (start, end) = (parse_code_number(start)?, parse_code_number(end)?);
+    if start > end {
+        return None;
+    }
+    Some((start..=end).map(|n| format!("E{:04}", n)).collect())
+}
+
+/// The codes in a range argument, erroring if it isn't a valid range or,
+/// without `force`, if it covers more than `MAX_RANGE` codes.
+fn range_codes(range: &str, force: bool) -> Result<Vec<String>, String> {
+    let codes =
+        expand_range(range).ok_or_else(|| format!("invalid error code range {:?}", range))?;
+    if codes.len() > MAX_RANGE && !force {
+        return Err(format!(
+            "{} covers {} codes; pass --force to explain more than {} at once",
+            range,
+            codes.len(),
+            MAX_RANGE
+        ));
+    }
+    Ok(codes)
+}
+
+/// Picks the error code whose explanation best matches a compiler message.
+fn match_code(message: &str) -> String {
+    let entries = index::build_index();
+    match index::best_match(&entries, message) {
+        Some((entry, confidence)) if confidence >= MIN_MATCH_CONFIDENCE => {
+            anstream::eprintln!(
+                "Best match: {} ({:.0}% confidence)",
+                Style::new().bold().paint(&entry.code),
+                confidence * 100.0
+            );
+            entry.code.clone()
+        }
+        Some((entry, confidence)) => error(&format!(
+            "no error code confidently matches {:?} (closest was {} at {:.0}%)",
+            message,
+            entry.code,
+            confidence * 100.0
+        )),
+        None => error(&format!("no error code matches {:?}", message)),
+    }
+}
+
+/// How output ends.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum TrailingNewline {
+    /// One newline on a terminal; piped output ends however rustc's does.
+    Auto,
+    Always,
+    Never,
+}
+
+/// Ends `output` with exactly one newline or none, as `mode` asks. `auto`
+/// decides what `Auto` means.
+fn end_output(output: String, mode: TrailingNewline, auto: TrailingNewline) -> String {
+    let mode = match mode {
+        TrailingNewline::Auto => auto,
+        mode => mode,
+    };
+    match mode {
+        TrailingNewline::Auto => output,
+        TrailingNewline::Always => format!("{}\n", output.trim_end_matches('\n')),
+        TrailingNewline::Never => output.trim_end_matches('\n').to_string(),
+    }
+}
+
+/// Takes every escape sequence out of rendered `output` when color is
+/// `Never`, leaving its layout as it is.
+fn apply_color(output: String, color: ColorWhen) -> String {
+    if color == ColorWhen::Never {
+        anstream::adapter::strip_str(&output).to_string()
+    } else {
+        output
+    }
+}
+
+/// Drops the style reset at the very end of `output`, ahead of any final
+/// newlines. Resets inside the document are what keep code styling from
+/// bleeding into prose, but one at the very end is only tidiness.
+fn trim_trailing_reset(output: &mut String) {
+    let end = output.trim_end_matches('\n').len();
+    if output[..end].ends_with(ANSI_RESET) {
+        output.replace_range(end - ANSI_RESET.len()..end, "");
+    }
+}
+
+/// When to render styled output. `Auto` styles it only for a terminal,
+/// and otherwise passes rustc's output through.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ColorWhen {
+    Auto,
+    Always,
+    Never,
+}
+
+/// When to emit links as OSC 8 hyperlinks.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Hyperlinks {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Format {
+    Terminal,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum GroupBy {
+    None,
+    /// The first two digits of the code, like `E03xx`.
+    Prefix,
+    /// The category derived from the code's title.
+    Category,
+}
+
+/// Sorts explanations into groups, keeping codes in order within a group.
+/// Codes in `packages`, found in dependencies, are grouped by those instead.
+fn group_inputs(
+    inputs: Vec<(String, String)>,
+    group_by: GroupBy,
+    packages: &HashMap<String, String>,
+) -> Vec<(Option<String>, String, String)> {
+    let mut grouped: Vec<_> = inputs
         .into_iter()
-        .map(|block| map_block(block, syntax, ps, ts))
-        .collect()
+        .map(|(err_name, input)| {
+            let group = match group_by {
+                _ if packages.contains_key(&err_name) => packages.get(&err_name).cloned(),
+                GroupBy::None => None,
+                // Lints are grouped by their tool, like `clippy`
+                GroupBy::Prefix => match err_name.split_once("::") {
+                    Some((tool, _)) => Some(tool.to_string()),
+                    None => {
+                        let digits = err_name.trim_start_matches(['E', 'e']);
+                        Some(format!("E{}xx", digits.get(..2).unwrap_or(digits)))
+                    }
+                },
+                GroupBy::Category => Some(index::category_of(&index::title_of(&input)).to_string()),
+            };
+            (group, err_name, input)
+        })
+        .collect();
+    grouped.sort_by(|a, b| a.0.cmp(&b.0));
+    grouped
+}
+
+/// A list of the other error codes an explanation mentions, each with its
+/// title, and how to read them.
+fn related_footer(err_name: &str, input: &str, options: &Options) -> Option<String> {
+    let entries: Vec<index::IndexEntry> = related_codes(input, err_name)
+        .iter()
+        .filter_map(|code| index::probe(code))
+        .filter(|entry| entry.has_explanation)
+        .collect();
+    if entries.is_empty() {
+        return None;
+    }
+    let accent = if options.mono {
+        Style::new().bold()
+    } else {
+        AnsiColor::Cyan.on_default().bold()
+    };
+    let mut footer = Style::new().bold().underline().paint("Related errors");
+    for entry in &entries {
+        footer.push_str(&format!(
+            "\n  {}  {}",
+            accent.paint(&entry.code),
+            entry.title
+        ));
+    }
+    let hint = format!(
+        "Run `{} <code>` to read any of these.",
+        options.command_name
+    );
+    footer.push_str(&format!("\n\n{}", Style::new().dimmed().paint(hint)));
+    Some(footer)
 }
 
-fn highlight_code(code: &str, syntax: &SyntaxReference, ps: &SyntaxSet, ts: &ThemeSet) -> String {
-    let mut output = String::with_capacity(code.len());
-    let mut h = HighlightLines::new(syntax, &ts.themes[SYNTECT_THEME]);
-    let mut input = String::from("\n");
-    input.push_str(code);
-    let ranges = h.highlight(&input, ps);
-    let escaped = as_24_bit_terminal_escaped(&ranges, true);
-    output.push_str(&escaped);
-    output.push_str(ANSI_RESET);
-    output
+/// The header above each of several explanations: the code on a bar, with
+/// a rule out to the wrap width.
+fn code_header(err_name: &str, render_options: &RenderOptions) -> String {
+    let badge = format!(" {} ", err_name);
+    let rule = glyphs(render_options.ascii)
+        .rule
+        .repeat(render_options.width.saturating_sub(badge.width() + 1));
+    format!(
+        "{} {}",
+        Style::new().bold().invert().paint(badge),
+        Style::new().dimmed().paint(rule)
+    )
+}
+
+/// A note on how often a code came up in a build, like "2 errors in this
+/// build, first at src/main.rs:6:18".
+fn occurrences(count: usize, location: &Option<String>, build: &str) -> String {
+    let plural = if count == 1 { "" } else { "s" };
+    let mut note = format!("{} error{} in {}", count, plural, build);
+    if let Some(location) = location {
+        note.push_str(&format!(", first at {}", location));
+    }
+    note
+}
+
+/// Asks for codes on stdin, for when none were given on the command line.
+fn prompt_for_codes() -> io::Result<Vec<String>> {
+    eprint!("Enter an error code: ");
+    io::stderr().flush()?;
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    Ok(line.split_whitespace().map(str::to_string).collect())
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    #[cfg(windows)]
-    let _ = ansi_term::enable_ansi_support();
+    // As RUSTC_WRAPPER, rustc runs before anything is loaded, which only a
+    // failed compile needs. Settings then come from the config and
+    // environment alone.
+    let wrapped = match wrapper::rustc_args() {
+        Some(args) => {
+            let (status, codes) = wrapper::run(&args)
+                .unwrap_or_else(|e| error(&format!("couldn't run {:?}: {}", args[0], e)));
+            if status.success() || codes.is_empty() {
+                process::exit(status.code().unwrap_or(1));
+            }
+            Some((args, status, codes))
+        }
+        None => None,
+    };
+    let args = match &wrapped {
+        Some(_) => env::args().take(1).collect(),
+        None => env::args().collect(),
+    };
 
     let ps = SyntaxSet::load_defaults_newlines();
-    let ts = ThemeSet::load_defaults();
-    let syntax = ps.find_syntax_by_extension("rs").unwrap();
-
-    let mut args = env::args().peekable();
-    let command_name = {
-        let mut command_name = args
-            .next()
-            .and_then(|path| {
-                PathBuf::from(path)
-                    .file_name()
-                    .map(|f| f.to_string_lossy().into_owned())
-            })
-            .unwrap();
-        // Was this invoked via cargo-explain instead of directly?
-        if Some(&"explain".to_string()) == args.peek() {
-            args.next();
-            command_name = "cargo explain".to_string();
+    let mut ts = ThemeSet::load_defaults();
+    for problem in themes::add_user_themes(&mut ts) {
+        warn(&problem);
+    }
+
+    let mut options = parse_args(args);
+    match &options.theme {
+        Some(theme) => {
+            if let Err(e) = find_theme(&ts, theme) {
+                error(&e);
+            }
+        }
+        // bat's own themes aren't all among syntect's, so one that isn't
+        // loaded is passed over rather than an error
+        None => {
+            options.theme = themes::bat_theme()
+                .filter(|theme| ts.themes.contains_key(theme))
+                .or_else(|| {
+                    let light = atty::is(atty::Stream::Stdout)
+                        && options.color != ColorWhen::Never
+                        && term::background() == Some(term::Background::Light);
+                    Some(DEFAULT_LIGHT_THEME.to_string()).filter(|_| light)
+                })
+        }
+    }
+    let command_name = &options.command_name;
+
+    if options.index {
+        let entries = index::build_index();
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&index::index_json(&entries))?
+        );
+        return Ok(());
+    }
+
+    let locale = options.locale.as_deref().filter(|&locale| {
+        let supported = locale_supported(locale);
+        if !supported {
+            warn(&format!(
+                "rustc can't translate explanations into {}, showing them in English",
+                locale
+            ));
+        }
+        supported
+    });
+
+    if let Some((args, status, codes)) = wrapped {
+        let render_options = render_options(&options);
+        let styled = match options.color {
+            ColorWhen::Auto => wrapper::wants_color(&args),
+            color => color == ColorWhen::Always,
+        };
+        wrapper::explain(&codes, &ps, &ts, &render_options, styled, locale)?;
+        process::exit(status.code().unwrap_or(1));
+    }
+
+    if options.pipe {
+        let render_options = render_options(&options);
+        let styled = match options.color {
+            ColorWhen::Auto => atty::is(atty::Stream::Stdout),
+            color => color == ColorWhen::Always,
+        };
+        pipe::pipe(&ps, &ts, &render_options, styled, locale)?;
+        return Ok(());
+    }
+
+    if options.watch {
+        let root = diagnostics::project_root()
+            .unwrap_or_else(|| error("--watch needs to be run in a Cargo project"));
+        let render_options = render_options(&options);
+        let styled = match options.color {
+            ColorWhen::Auto => atty::is(atty::Stream::Stdout),
+            color => color == ColorWhen::Always,
+        };
+        return watch::watch(&root, &ps, &ts, &render_options, styled, locale);
+    }
+
+    if options.serve {
+        let render_options = render_options(&options);
+        serve::serve(
+            &ps,
+            &ts,
+            &render_options,
+            options.format,
+            options.json_render,
+            locale,
+        )?;
+        return Ok(());
+    }
+
+    let prompt = options
+        .prompt
+        .unwrap_or_else(|| atty::is(atty::Stream::Stdin) && atty::is(atty::Stream::Stdout));
+    // Notes shown alongside a code's explanation, like where it was found
+    let mut notes = HashMap::new();
+    // The source where a code's first error was, by code
+    let mut snippets = HashMap::new();
+    let comment_codes = options.explain_all_in.as_ref().map(|path| {
+        let found = scan::codes_in_comments(path)
+            .unwrap_or_else(|e| error(&format!("couldn't read {}: {}", path.display(), e)));
+        if found.is_empty() {
+            error(&format!(
+                "no error codes found in comments in {}",
+                path.display()
+            ));
         }
-        command_name
+        let mut codes = Vec::new();
+        for (code, lines) in found {
+            let lines: Vec<String> = lines.iter().map(usize::to_string).collect();
+            let plural = if lines.len() == 1 { "" } else { "s" };
+            let note = format!(
+                "found in {} on line{} {}",
+                path.display(),
+                plural,
+                lines.join(", ")
+            );
+            notes.insert(code.clone(), note);
+            codes.push(code);
+        }
+        codes
+    });
+
+    let message = match &options.from_message {
+        Some(Some(message)) => Some(message.clone()),
+        Some(None) => {
+            if atty::is(atty::Stream::Stdin) {
+                eprintln!("Paste the compiler output, then press Ctrl-D:");
+            }
+            let mut message = String::new();
+            io::stdin().read_to_string(&mut message)?;
+            Some(message)
+        }
+        None => None,
     };
+    let message_codes = message.map(|message| scan::codes_in_message(&message));
+    if message_codes.as_ref().is_some_and(Vec::is_empty) {
+        error("no error codes found in the compiler output");
+    }
 
-    let err_name = if let Some(idx) =
-        env::args().enumerate().find_map(
-            |(idx, arg)| {
-                if arg == "--explain" {
-                    Some(idx)
-                } else {
-                    None
-                }
-            },
-        ) {
-        env::args().nth(idx + 1)
+    let log = match &options.log {
+        Some(Some(path)) if path.as_os_str() != "-" => Some(
+            fs::read_to_string(path)
+                .unwrap_or_else(|e| error(&format!("couldn't read {}: {}", path.display(), e))),
+        ),
+        Some(_) => {
+            let mut log = String::new();
+            io::stdin().read_to_string(&mut log)?;
+            Some(log)
+        }
+        None => None,
+    };
+    let log_codes = log.map(|log| {
+        let errors = scan::errors_in_log(&log);
+        if errors.is_empty() {
+            error("no errors with codes found in the build log");
+        }
+        for (code, count, location) in &errors {
+            notes.insert(code.clone(), occurrences(*count, location, "the log"));
+        }
+        errors
+            .into_iter()
+            .map(|(code, _, _)| code)
+            .collect::<Vec<_>>()
+    });
+
+    // The dependencies each lint in a future incompatibility report is in
+    let mut packages = HashMap::new();
+    let future_codes = options.future_incompat.then(|| {
+        let report = future_incompat::report().unwrap_or_else(|e| error(&e));
+        let findings = future_incompat::findings(&report);
+        if findings.is_empty() {
+            error("no lints found in the future incompatibility report");
+        }
+        let mut codes: Vec<String> = Vec::new();
+        for finding in &findings {
+            let plural = if finding.count == 1 { "" } else { "s" };
+            let mut note = format!("{} warning{} in {}", finding.count, plural, finding.package);
+            if let Some(location) = &finding.location {
+                note.push_str(&format!(", first at {}", location));
+            }
+            notes
+                .entry(finding.code.clone())
+                .and_modify(|notes: &mut String| *notes = format!("{}\n{}", notes, note))
+                .or_insert(note);
+            if !codes.contains(&finding.code) {
+                codes.push(finding.code.clone());
+            }
+        }
+        packages = future_incompat::packages_by_code(&findings);
+        codes
+    });
+
+    // With nothing else to go on, a project's own build says what to explain
+    let build_codes = if options.err_names.is_empty()
+        && message_codes.is_none()
+        && log_codes.is_none()
+        && future_codes.is_none()
+        && comment_codes.is_none()
+        && options.match_message.is_none()
+        && options.prompt != Some(true)
+        && diagnostics::in_cargo_project()
+    {
+        let diagnostics = diagnostics::cargo_check()
+            .unwrap_or_else(|e| error(&format!("couldn't run cargo check: {}", e)));
+        let codes = diagnostics::error_codes(&diagnostics);
+        if codes.is_empty() {
+            eprintln!("cargo check found no errors with explanations.");
+            return Ok(());
+        }
+        for found in &codes {
+            let location = &found.first.location;
+            notes.insert(
+                found.code.clone(),
+                occurrences(found.count, location, "this build"),
+            );
+            if let (Some(snippet), Some(location)) = (&found.first.snippet, location) {
+                snippets.insert(found.code.clone(), (location.clone(), snippet.clone()));
+            }
+        }
+        Some(codes.into_iter().map(|found| found.code).collect())
     } else {
-        args.next()
-    }
-    .unwrap_or_else(|| {
+        None
+    };
+
+    let requested = match (options.err_names.clone(), &options.match_message) {
+        _ if message_codes.is_some() => message_codes.unwrap_or_default(),
+        _ if log_codes.is_some() => log_codes.unwrap_or_default(),
+        _ if future_codes.is_some() => future_codes.unwrap_or_default(),
+        _ if build_codes.is_some() => build_codes.unwrap_or_default(),
+        _ if comment_codes.is_some() => comment_codes.unwrap_or_default(),
+        (_, Some(message)) => vec![match_code(message)],
+        (codes, None) if !codes.is_empty() => codes,
+        (_, None) if prompt => prompt_for_codes()?,
+        (_, None) => Vec::new(),
+    };
+    if requested.is_empty() {
         let strings = &[
-            Color::Red.bold().paint("error"),
+            AnsiColor::Red.on_default().bold().paint("error"),
             Style::default().bold().paint(": missing error number to "),
-            Style::default().bold().paint(&command_name),
+            Style::default().bold().paint(command_name),
             Style::default().bold().paint("."),
             Style::default().paint("\nUsage: "),
-            Style::default().paint(&command_name),
+            Style::default().paint(command_name),
             Style::default().paint(" --explain <error number>"),
         ];
-        eprintln!("{}", ANSIStrings(strings));
+        anstream::eprintln!("{}", strings.concat());
         process::exit(1);
-    });
+    }
+
+    // Pairs each code with whether it came from a range, since ranges are
+    // allowed to span gaps in the error index.
+    let mut err_names = Vec::new();
+    for arg in requested {
+        if arg.contains("..") {
+            let codes = range_codes(&arg, options.force).unwrap_or_else(|e| error(&e));
+            err_names.extend(codes.into_iter().map(|code| (code, true)));
+        } else {
+            // Anything that isn't a code is left for rustc to reject
+            err_names.push((normalize_code(&arg).unwrap_or(arg), false));
+        }
+    }
+    // Codes are explained in the order they were asked for, each once
+    let mut seen = HashSet::new();
+    err_names.retain(|(code, _)| seen.insert(code.clone()));
+    let batch = err_names.len() > 1 || err_names[0].1 || !notes.is_empty();
+
+    if options.copy_url {
+        let url = docs_url(&options.docs_base, &err_names[0].0);
+        match copy_to_clipboard(&url) {
+            Ok(()) => eprintln!("Copied {} to clipboard", url),
+            Err(e) => warn(&format!("couldn't copy URL to clipboard: {}", e)),
+        }
+    }
+
+    if options.open {
+        for (err_name, _) in &err_names {
+            let url = docs_url(&options.docs_base, err_name);
+            if let Err(e) = open_in_browser(&url) {
+                error(&format!("couldn't open {}: {}", url, e));
+            }
+        }
+        return Ok(());
+    }
+
+    if let Some((a, b)) = &options.diff_toolchains {
+        for toolchain in [a, b] {
+            toolchain::check(toolchain).unwrap_or_else(|e| error(&e));
+        }
+        let mut unknown = Vec::new();
+        let mut stdout = styled_stdout(options.color != ColorWhen::Never);
+        for (err_name, _) in &err_names {
+            let before = toolchain_explanation(a, err_name, locale)?;
+            let after = toolchain_explanation(b, err_name, locale)?;
+            if before.is_none() && after.is_none() {
+                unknown.push(err_name.clone());
+                continue;
+            }
+            let (before, after) = (before.unwrap_or_default(), after.unwrap_or_default());
+            let (red, green) = if options.mono {
+                (Style::new(), Style::new())
+            } else {
+                (AnsiColor::Red.on_default(), AnsiColor::Green.on_default())
+            };
+            writeln!(stdout, "{}", Style::new().bold().paint(err_name))?;
+            writeln!(stdout, "{}", red.paint(format!("--- {}", a)))?;
+            writeln!(stdout, "{}\n", green.paint(format!("+++ {}", b)))?;
+            if before == after {
+                writeln!(
+                    stdout,
+                    "{}\n",
+                    Style::new().dimmed().paint("No differences.")
+                )?;
+            } else {
+                writeln!(stdout, "{}", diff_words(&before, &after, options.mono))?;
+            }
+        }
+        return report_unknown(&unknown);
+    }
 
-    if !atty::is(atty::Stream::Stdout) {
-        let status = Command::new("rustc")
-            .args(&["--explain", &err_name])
-            .status()?;
-        process::exit(status.code().unwrap_or(0));
+    if !batch
+        && options.format == Format::Terminal
+        && options.diff_themes.is_none()
+        && options.trailing_newline == TrailingNewline::Auto
+        && !atty::is(atty::Stream::Stdout)
+        && options.color != ColorWhen::Always
+    {
+        // rustc's lints have no `--explain` to pass through to, and stored
+        // indexes no rustc
+        if !lints::is_lint_name(&err_names[0].0) && !toolchain::without_rustc() {
+            match explain_command(&err_names[0].0, locale).status() {
+                Ok(status) => {
+                    if !status.success() {
+                        suggest_codes(&[err_names[0].0.clone()]);
+                    }
+                    process::exit(status.code().unwrap_or(0));
+                }
+                Err(e) if toolchain::fall_back_to_bundle(&e) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        match fetch_explanation(&err_names[0].0, locale)? {
+            Some(input) => io::stdout().write_all(input.as_bytes())?,
+            None => return report_unknown(&[err_names[0].0.clone()]),
+        }
+        return Ok(());
     }
 
-    let input = {
-        let result = Command::new("rustc")
-            .args(&["--explain", &err_name])
-            .stderr(Stdio::inherit())
-            .output()?;
-        if !result.status.success() {
-            process::exit(result.status.code().unwrap_or(1));
+    // Codes asked for by name that rustc couldn't explain, reported once
+    // everything else has been shown
+    let mut unknown = Vec::new();
+    let inputs = if batch {
+        let mut inputs = Vec::new();
+        for (err_name, from_range) in err_names {
+            match fetch_explanation(&err_name, locale)? {
+                Some(input) => inputs.push((err_name, input)),
+                None if from_range => {}
+                None => unknown.push(err_name),
+            }
+        }
+        if inputs.is_empty() {
+            return report_unknown(&unknown);
+        }
+        inputs
+    } else {
+        let explained = if lints::is_lint_name(&err_names[0].0) || toolchain::without_rustc() {
+            None
+        } else {
+            let command = explain_command(&err_names[0].0, locale)
+                .stderr(Stdio::inherit())
+                .output();
+            match command {
+                Ok(result) => Some(result),
+                Err(e) if toolchain::fall_back_to_bundle(&e) => None,
+                Err(e) => return Err(e.into()),
+            }
+        };
+        match explained {
+            Some(result) => {
+                if !result.status.success() {
+                    suggest_codes(&[err_names[0].0.clone()]);
+                    process::exit(result.status.code().unwrap_or(1));
+                }
+                let input = String::from_utf8(result.stdout)
+                    .expect("rustc --explain terminal output wasn't valid utf-8");
+                vec![(err_names[0].0.clone(), input)]
+            }
+            None => match fetch_explanation(&err_names[0].0, locale)? {
+                Some(input) => vec![(err_names[0].0.clone(), input)],
+                None => return report_unknown(&[err_names[0].0.clone()]),
+            },
         }
-        String::from_utf8(result.stdout)
-            .expect("rustc --explain terminal output wasn't valid utf-8")
     };
 
-    let blox = tokenize(&input);
-    let mapped = blox
+    if let Some((a, b)) = &options.diff_themes {
+        let mut stdout = styled_stdout(options.color != ColorWhen::Never);
+        for (err_name, input) in &inputs {
+            if inputs.len() > 1 {
+                writeln!(
+                    stdout,
+                    "{}\n",
+                    Style::new().bold().underline().paint(err_name)
+                )?;
+            }
+            writeln!(
+                stdout,
+                "{}\n",
+                diff_themes(input, &ps, &ts, a, b, color_depth(&options), options.ascii)
+                    .unwrap_or_else(|e| error(&e))
+            )?;
+        }
+        return report_unknown(&unknown);
+    }
+
+    let render_options = render_options(&options);
+
+    if options.format == Format::Json {
+        let mut documents: Vec<_> = inputs
+            .iter()
+            .map(|(err_name, input)| {
+                let blocks = render_json(input, &ps, &ts, &render_options, options.json_render);
+                serde_json::json!({ "code": err_name, "blocks": blocks })
+            })
+            .collect();
+        let output = if batch {
+            serde_json::Value::Array(documents)
+        } else {
+            documents.remove(0)
+        };
+        let output = serde_json::to_string_pretty(&output)?;
+        print!(
+            "{}",
+            end_output(output, options.trailing_newline, TrailingNewline::Always)
+        );
+        return report_unknown(&unknown);
+    }
+
+    if !atty::is(atty::Stream::Stdout) && options.color != ColorWhen::Always {
+        let mut output = String::new();
+        let mut current_group = None;
+        for (group, err_name, input) in group_inputs(inputs, options.group_by, &packages) {
+            if group.is_some() && group != current_group {
+                let name = group.as_deref().unwrap_or_default();
+                output.push_str(&format!("== {} ==\n\n", name));
+                current_group = group;
+            }
+            if batch {
+                output.push_str(&err_name);
+                if let Some(note) = notes.get(&err_name) {
+                    output.push_str(&format!("\n{}", note));
+                }
+                output.push_str("\n\n");
+                if let Some((location, snippet)) = snippets.get(&err_name) {
+                    output.push_str(&format!("{}\n", snippet.render(location, false, true)));
+                }
+                output.push_str(&input);
+                output.push('\n');
+            } else {
+                output.push_str(&input);
+            }
+        }
+        print!(
+            "{}",
+            end_output(output, options.trailing_newline, TrailingNewline::Auto)
+        );
+        return report_unknown(&unknown);
+    }
+
+    // The line kept at the top of the pager, naming what's being read
+    let title = match inputs.as_slice() {
+        [(err_name, input)] if options.pin_title => {
+            // Cut to one screen line, so it's exactly the one line pinned
+            let width = render_options.width;
+            let mut title = format!(" {}  {} ", err_name, index::title_of(input));
+            if title.width() > width {
+                let ellipsis = format!("{} ", glyphs(options.ascii).ellipsis);
+                let mut cut = String::new();
+                for c in title.chars() {
+                    if cut.width() + c.width().unwrap_or(0) > width.saturating_sub(ellipsis.width())
+                    {
+                        break;
+                    }
+                    cut.push(c);
+                }
+                title = cut + &ellipsis;
+            }
+            Some(Style::new().bold().invert().paint(title))
+        }
+        _ => None,
+    };
+    let multiple = inputs.len() > 1 || !notes.is_empty();
+    let mut current_group = None;
+    let mut output = group_inputs(inputs, options.group_by, &packages)
         .into_iter()
-        .map(|b| map_block(b, &syntax, &ps, &ts))
-        .collect();
-    let mut output = generate_markdown(mapped);
-    // We add an extra newline at the start of highlighted code
-    // blocks to make the first line's background appear to span
-    // the entire line. Here, we remove the a newline before that
-    // to balance it out.
-    output = output.replace("\n\n\x1B", "\n\x1B");
+        .map(|(group, err_name, input)| {
+            let mut section = String::new();
+            if group.is_some() && group != current_group {
+                let name = group.as_deref().unwrap_or_default();
+                let color = if options.mono {
+                    Style::new()
+                } else {
+                    AnsiColor::Cyan.on_default()
+                };
+                let rule = glyphs(options.ascii).heavy_rule.repeat(2);
+                let header = color.bold().paint(format!("{} {} {}", rule, name, rule));
+                section.push_str(&format!("{}\n\n", header));
+                current_group = group;
+            }
+            let body = render(&input, &ps, &ts, &render_options, None);
+            if multiple {
+                section.push_str(&format!("{}\n", code_header(&err_name, &render_options)));
+                if let Some(note) = notes.get(&err_name) {
+                    section.push_str(&format!("{}\n", Style::new().dimmed().paint(note)));
+                }
+                section.push('\n');
+                if let Some((location, snippet)) = snippets.get(&err_name) {
+                    let snippet = snippet.render(location, true, options.mono);
+                    section.push_str(&format!("{}\n", snippet));
+                }
+            }
+            section.push_str(&body);
+            if !options.no_related {
+                if let Some(footer) = related_footer(&err_name, &input, &options) {
+                    section.push_str(&format!("\n\n{}", footer));
+                }
+            }
+            section
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    if options.no_trailing_reset {
+        trim_trailing_reset(&mut output);
+    }
+
+    if let Some(title) = &title {
+        output = format!("{}\n{}", title, output);
+    }
+    let output = end_output(output, options.trailing_newline, TrailingNewline::Always);
+    let output = apply_color(output, options.color);
 
-    println!("{}", output);
+    match pager::resolve(options.pager.as_deref()) {
+        Some(command) if !options.no_pager && atty::is(atty::Stream::Stdout) => {
+            if title.is_some() && !pager::can_pin(&command) {
+                warn(&format!(
+                    "--pin-title needs less 600 or newer to pin the title, so it scrolls away \
+                     with the rest in {:?}",
+                    command
+                ));
+            }
+            pager::page(&command, &output, title.is_some() as usize)?
+        }
+        _ => write!(styled_stdout(true), "{}", output)?,
+    }
+
+    report_unknown(&unknown)
+}
 
+/// Fails the run over codes that couldn't be explained, once the rest have
+/// been shown.
+fn report_unknown(unknown: &[String]) -> Result<(), Box<dyn Error>> {
+    if !unknown.is_empty() {
+        suggest_codes(unknown);
+        error(&format!("no explanation found for {}", unknown.join(", ")));
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    /// The options a command line resolves to.
+    fn options(args: &[&str]) -> Options {
+        let args = ["cargo-explain"].iter().chain(args);
+        settings::resolve(Cli::try_parse_from(args).unwrap(), "cargo explain".into())
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn full_turns_max_blocks_off() {
+        let truncated = options(&["--max-blocks", "12"]);
+        assert_eq!(render_options(&truncated).max_blocks, Some(12));
+        let full = options(&["--max-blocks", "12", "--full"]);
+        assert_eq!(render_options(&full).max_blocks, None);
+    }
+
+    #[test]
+    fn normalizes_codes_as_typed_or_pasted() {
+        for typed in [
+            "E0308",
+            "e0308",
+            "0308",
+            "308",
+            "error[E0308]:",
+            "  E0308\n",
+            "E0308:",
+        ] {
+            assert_eq!(
+                normalize_code(typed).as_deref(),
+                Some("E0308"),
+                "{:?}",
+                typed
+            );
+        }
+        assert_eq!(normalize_code("e1").as_deref(), Some("E0001"));
+        assert_eq!(
+            normalize_code("Unused-Must-Use").as_deref(),
+            Some("unused_must_use")
+        );
+        assert_eq!(
+            normalize_code("clippy::Needless-Return").as_deref(),
+            Some("clippy::needless_return")
+        );
+        for not_a_code in ["", "E12345", "0308!", "error[]", "what is this"] {
+            assert_eq!(normalize_code(not_a_code), None, "{:?}", not_a_code);
+        }
+    }
+
+    #[test]
+    fn expands_inclusive_ranges() {
+        let codes = ["E0300", "E0301", "E0302"];
+        assert_eq!(expand_range("E0300..E0302").unwrap(), codes);
+        assert_eq!(expand_range("e300..=302").unwrap(), codes);
+        assert_eq!(expand_range("E0308..E0308").unwrap(), ["E0308"]);
+        assert_eq!(expand_range("E0302..E0300"), None);
+        assert_eq!(expand_range("E0300..lint"), None);
+        assert_eq!(expand_range("E0300.."), None);
+    }
+
+    #[test]
+    fn caps_ranges_without_force() {
+        assert_eq!(range_codes("E0001..E0100", false).unwrap().len(), MAX_RANGE);
+        assert!(range_codes("E0001..E0101", false).is_err());
+        assert_eq!(
+            range_codes("E0001..E0101", true).unwrap().len(),
+            MAX_RANGE + 1
+        );
+        assert!(range_codes("E0101..E0001", true).is_err());
+        assert!(options(&["--force", "E0001..E0999"]).force);
+    }
+
+    #[test]
+    fn explain_all_in_takes_no_codes() {
+        let args = ["cargo-explain", "--explain-all-in", "src/lib.rs", "E0308"];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn strips_the_subcommand_cargo_passes() {
+        // `cargo explain E0308` runs this
+        let argv = args(&["/home/me/.cargo/bin/cargo-explain", "explain", "E0308"]);
+        let (rewritten, command_name) = rewrite_args(argv);
+        assert_eq!(
+            rewritten,
+            args(&["/home/me/.cargo/bin/cargo-explain", "E0308"])
+        );
+        assert_eq!(command_name, "cargo explain");
+        assert_eq!(Cli::try_parse_from(rewritten).unwrap().codes, ["E0308"]);
+    }
+
+    #[test]
+    fn leaves_a_direct_run_alone() {
+        let argv = args(&["cargo-explain", "E0308"]);
+        let (rewritten, command_name) = rewrite_args(argv.clone());
+        assert_eq!(rewritten, argv);
+        assert_eq!(command_name, "cargo-explain");
+    }
+
+    #[test]
+    fn strips_only_the_first_explain() {
+        let (rewritten, _) = rewrite_args(args(&["cargo-explain", "explain", "explain"]));
+        assert_eq!(rewritten, args(&["cargo-explain", "explain"]));
+        // Under another name, there's no subcommand to strip
+        let argv = args(&["explainer", "explain", "E0308"]);
+        let (rewritten, command_name) = rewrite_args(argv.clone());
+        assert_eq!(rewritten, argv);
+        assert_eq!(command_name, "explainer");
+    }
+
+    #[test]
+    fn rewrites_plus_toolchain() {
+        let (rewritten, _) = rewrite_args(args(&["cargo-explain", "explain", "+nightly", "E0308"]));
+        assert_eq!(
+            rewritten,
+            args(&["cargo-explain", "--toolchain=nightly", "E0308"])
+        );
+        let cli = Cli::try_parse_from(rewritten).unwrap();
+        assert_eq!(cli.toolchain.as_deref(), Some("nightly"));
+        assert_eq!(cli.codes, ["E0308"]);
+        // Only first, as cargo takes it
+        let argv = args(&["cargo-explain", "E0308", "+nightly"]);
+        assert_eq!(rewrite_args(argv.clone()).0, argv);
+    }
+
+    #[test]
+    fn compact_profile_presets() {
+        let compact = options(&["--profile", "compact"]);
+        assert_eq!(compact.indent, 1);
+        assert!(compact.merge_code);
+        assert!(matches!(compact.code_bg, CodeBackground::None));
+
+        let overridden = options(&[
+            "--profile",
+            "compact",
+            "--indent",
+            "3",
+            "--code-bg",
+            "#102030",
+        ]);
+        assert_eq!(overridden.indent, 3);
+        assert!(overridden.merge_code);
+        assert!(matches!(overridden.code_bg, CodeBackground::Custom(_)));
+
+        let switched_off = options(&["--profile", "compact", "--no-merge-code"]);
+        assert!(!switched_off.merge_code);
+        assert_eq!(switched_off.indent, 1);
+    }
+
+    #[test]
+    fn docs_profile_presets() {
+        let docs = options(&["--profile", "docs"]);
+        assert!(docs.toc);
+        assert!(docs.pad_code);
+        assert!(docs.columns);
+        assert!(render_options(&docs).toc);
+
+        let overridden = options(&["--profile", "docs", "--no-toc"]);
+        assert!(overridden.pad_code);
+        assert!(!render_options(&overridden).toc);
+
+        let switched_off = options(&["--profile", "docs", "--no-pad-code", "--no-columns"]);
+        assert!(!switched_off.pad_code);
+        assert!(!switched_off.columns);
+        assert!(switched_off.toc);
+    }
+
+    #[test]
+    fn accessible_profile_presets() {
+        let accessible = options(&["--profile", "accessible"]);
+        assert!(accessible.mono);
+        assert!(matches!(accessible.code_bg, CodeBackground::None));
+        assert_eq!(accessible.indent, 4);
+
+        let overridden = options(&[
+            "--profile",
+            "accessible",
+            "--indent",
+            "2",
+            "--code-bg",
+            "#000000",
+        ]);
+        assert!(overridden.mono);
+        assert_eq!(overridden.indent, 2);
+        assert!(matches!(overridden.code_bg, CodeBackground::Custom(_)));
+
+        let switched_off = options(&["--profile", "accessible", "--no-mono"]);
+        assert!(!switched_off.mono);
+        assert_eq!(switched_off.indent, 4);
+    }
+
+    #[test]
+    fn no_trailing_reset_leaves_the_final_reset_off() {
+        let ps = SyntaxSet::load_defaults_newlines();
+        let ts = ThemeSet::load_defaults();
+        let options = RenderOptions::default();
+        let rendered = render(
+            "Ends in code:\n\n```\nlet x = 1;\n```\n",
+            &ps,
+            &ts,
+            &options,
+            None,
+        );
+        assert!(rendered.ends_with(ANSI_RESET));
+
+        let mut output = rendered.clone();
+        trim_trailing_reset(&mut output);
+        assert!(!output.ends_with(ANSI_RESET));
+        assert_eq!(output.len(), rendered.len() - ANSI_RESET.len());
+        // Resets within the document stay
+        assert!(output.contains(ANSI_RESET));
+
+        let mut output = format!("{}\n", rendered);
+        trim_trailing_reset(&mut output);
+        assert!(!output.ends_with(&format!("{}\n", ANSI_RESET)));
+        assert!(output.ends_with('\n'));
+    }
+
+    /// `output` ended as `mode` asks, with its final reset trimmed first
+    /// when `no_trailing_reset` is set, as `main` does.
+    fn ended(output: &str, mode: TrailingNewline, auto: TrailingNewline, trim: bool) -> String {
+        let mut output = output.to_string();
+        if trim {
+            trim_trailing_reset(&mut output);
+        }
+        end_output(output, mode, auto)
+    }
+
+    #[test]
+    fn trailing_newline_always() {
+        let always = TrailingNewline::Always;
+        for auto in [TrailingNewline::Auto, TrailingNewline::Always] {
+            assert_eq!(ended("text", always, auto, false), "text\n");
+            assert_eq!(ended("text\n\n\n", always, auto, false), "text\n");
+            assert_eq!(ended("text\x1B[0m", always, auto, false), "text\x1B[0m\n");
+            assert_eq!(ended("text\x1B[0m\n\n", always, auto, true), "text\n");
+        }
+    }
+
+    #[test]
+    fn trailing_newline_never() {
+        let never = TrailingNewline::Never;
+        for auto in [TrailingNewline::Auto, TrailingNewline::Always] {
+            assert_eq!(ended("text", never, auto, false), "text");
+            assert_eq!(ended("text\n\n", never, auto, false), "text");
+            assert_eq!(ended("text\x1B[0m\n", never, auto, false), "text\x1B[0m");
+            assert_eq!(ended("text\x1B[0m\n", never, auto, true), "text");
+        }
+    }
+
+    #[test]
+    fn trailing_newline_auto() {
+        let auto = TrailingNewline::Auto;
+        // Piped output ends however rustc's did
+        assert_eq!(ended("text", auto, TrailingNewline::Auto, false), "text");
+        assert_eq!(
+            ended("text\n\n", auto, TrailingNewline::Auto, false),
+            "text\n\n"
+        );
+        assert_eq!(
+            ended("text\x1B[0m\n", auto, TrailingNewline::Auto, true),
+            "text\n"
+        );
+        // and output for a terminal with exactly one newline
+        assert_eq!(
+            ended("text", auto, TrailingNewline::Always, false),
+            "text\n"
+        );
+        assert_eq!(
+            ended("text\n\n", auto, TrailingNewline::Always, false),
+            "text\n"
+        );
+        assert_eq!(
+            ended("text\x1B[0m", auto, TrailingNewline::Always, false),
+            "text\x1B[0m\n"
+        );
+        assert_eq!(
+            ended("text\x1B[0m", auto, TrailingNewline::Always, true),
+            "text\n"
+        );
+    }
+
+    #[test]
+    fn no_color_renders_without_escapes() {
+        env::set_var("NO_COLOR", "1");
+        let options = options(&["--hyperlinks", "always"]);
+        env::remove_var("NO_COLOR");
+        assert!(options.color == ColorWhen::Never);
+        assert!(!options.mono);
+
+        let ps = SyntaxSet::load_defaults_newlines();
+        let ts = ThemeSet::load_defaults();
+        let render_options = render_options(&options);
+        let input = "# A *title*\n\nSee [the **book**](https://example.com) and `code`.\n\n\
+                     ```\nfn main() {}\n```\n";
+        let output = render(input, &ps, &ts, &render_options, None);
+        assert!(output.contains('\x1B'));
+        let output = apply_color(output, options.color);
+        assert!(!output.contains('\x1B'), "{:?}", output);
+        assert!(output.contains("See the book (https://example.com) and code."));
+        assert!(output.contains("fn main() {}"));
+    }
+}
@@ -0,0 +1,95 @@
+use std::env;
+use std::ffi::OsString;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Picks the pager command: `--pager` wins over `$PAGER`, and an empty
+/// command means "don't page".
+pub fn resolve(pager_flag: Option<&str>) -> Option<String> {
+    let command = match pager_flag {
+        Some(command) => command.to_string(),
+        None => env::var("PAGER").ok()?,
+    };
+    if command.trim().is_empty() {
+        None
+    } else {
+        Some(command)
+    }
+}
+
+fn is_less(program: &str) -> bool {
+    Path::new(program)
+        .file_stem()
+        .is_some_and(|stem| stem == "less")
+}
+
+/// less only passes escape sequences through when given `-R`, so make sure
+/// it's present in `$LESS` unless the command line already asks for it.
+fn less_env(args: &[&str]) -> Option<OsString> {
+    let has_raw = args.iter().any(|arg| match arg.strip_prefix("--") {
+        Some(long) => long == "RAW-CONTROL-CHARS" || long == "raw-control-chars",
+        None => arg.starts_with('-') && arg.contains(['r', 'R']),
+    });
+    if has_raw {
+        return None;
+    }
+    let mut less = env::var_os("LESS").unwrap_or_default();
+    less.push("R");
+    Some(less)
+}
+
+/// Whether the pager `command` can pin lines to the top of the screen,
+/// which only less 600 and newer can.
+pub fn can_pin(command: &str) -> bool {
+    let program = command.split_whitespace().next().unwrap_or_default();
+    is_less(program) && less_has_header(program)
+}
+
+/// Whether this less can pin lines to the top of the screen with
+/// `--header`, which arrived in less 600.
+fn less_has_header(program: &str) -> bool {
+    let version = match Command::new(program).arg("--version").output() {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).into_owned(),
+        Err(_) => return false,
+    };
+    version
+        .split_whitespace()
+        .nth(1)
+        .and_then(|number| number.parse::<u32>().ok())
+        .is_some_and(|number| number >= 600)
+}
+
+/// Writes `output` through the pager, falling back to stdout if it can't be
+/// spawned. With `pinned` lines, less keeps that many lines from the top of
+/// the output on screen while the rest scrolls, if it's new enough to.
+pub fn page(command: &str, output: &str, pinned: usize) -> io::Result<()> {
+    let mut words = command.split_whitespace();
+    let program = words.next().unwrap_or_default();
+    let args: Vec<&str> = words.collect();
+
+    let mut cmd = Command::new(program);
+    cmd.args(&args).stdin(Stdio::piped());
+    if is_less(program) {
+        if let Some(less) = less_env(&args) {
+            cmd.env("LESS", less);
+        }
+        if pinned > 0 && can_pin(program) {
+            cmd.arg(format!("--header={}", pinned));
+        }
+    }
+
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(_) => {
+            print!("{}", output);
+            return Ok(());
+        }
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // The user quitting the pager early closes the pipe; that's fine.
+        let _ = write!(stdin, "{}", output);
+    }
+    child.wait()?;
+    Ok(())
+}
@@ -0,0 +1,60 @@
+//! `--pipe`: cargo's JSON diagnostics in on stdin, each one out as the
+//! compiler would print it, followed the first time its code comes up by
+//! that code's explanation.
+
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+
+use cargo_explain::{render, RenderOptions};
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+
+use crate::diagnostics::Diagnostic;
+use crate::{code_header, fetch_explanation, styled_stdout, warn};
+
+/// Follows the build until stdin closes, writing as each line comes in so
+/// explanations show up while the build is still going. Lines that aren't
+/// JSON, like cargo's own output mixed in, pass through as they are.
+/// Explanations are rendered when `styled`, and otherwise written as rustc
+/// wrote them.
+pub fn pipe(
+    ps: &SyntaxSet,
+    ts: &ThemeSet,
+    options: &RenderOptions,
+    styled: bool,
+    locale: Option<&str>,
+) -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut stdout = styled_stdout(styled);
+    let mut explained = HashSet::new();
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if !line.starts_with('{') {
+            writeln!(stdout, "{}", line)?;
+            stdout.flush()?;
+            continue;
+        }
+        let diagnostic = match Diagnostic::parse(&line) {
+            Some(diagnostic) => diagnostic,
+            None => continue,
+        };
+        if let Some(rendered) = &diagnostic.rendered {
+            write!(stdout, "{}", rendered)?;
+        }
+        if let Some(code) = diagnostic.code {
+            if explained.insert(code.clone()) {
+                match fetch_explanation(&code, locale) {
+                    Ok(Some(input)) if styled => {
+                        writeln!(stdout, "{}\n", code_header(&code, options))?;
+                        writeln!(stdout, "{}\n", render(&input, ps, ts, options, None))?;
+                    }
+                    Ok(Some(input)) => writeln!(stdout, "{}\n\n{}", code, input)?,
+                    Ok(None) => {}
+                    Err(e) => warn(&format!("couldn't explain {}: {}", code, e)),
+                }
+            }
+        }
+        stdout.flush()?;
+    }
+    Ok(())
+}
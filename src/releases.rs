@@ -0,0 +1,172 @@
+//! `--release`: the error index as an earlier Rust release documented it,
+//! downloaded once from static.rust-lang.org into the cache directory.
+
+use regex::Regex;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::html;
+use crate::index::{self, IndexEntry};
+
+const DIST: &str = "https://static.rust-lang.org/dist";
+
+/// The rust-docs component is the same for every target, so any will do.
+const DOCS_TARGET: &str = "x86_64-unknown-linux-gnu";
+
+/// `$XDG_CACHE_HOME/cargo-explain`, or `~/.cache/cargo-explain`.
+fn cache_dir() -> Option<PathBuf> {
+    let cache_home = env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| Some(PathBuf::from(env::var_os("HOME")?).join(".cache")))?;
+    Some(cache_home.join("cargo-explain"))
+}
+
+/// The directory holding a release's explanations, one markdown file per
+/// code, downloading them first if they aren't cached yet.
+pub fn fetch(version: &str) -> Result<PathBuf, String> {
+    if !Regex::new(r"^\d+\.\d+(\.\d+)?$").unwrap().is_match(version) {
+        return Err(format!(
+            "invalid release {:?}, expected a version like 1.70.0",
+            version
+        ));
+    }
+    let releases = cache_dir()
+        .ok_or("couldn't find a cache directory to keep releases in")?
+        .join("releases");
+    let dir = releases.join(version);
+    if dir.is_dir() {
+        return Ok(dir);
+    }
+    eprintln!("Downloading the error index of Rust {}...", version);
+    // Unpacked beside where it goes, and moved there once it's all there
+    let partial = releases.join(format!("{}.partial", version));
+    let _ = fs::remove_dir_all(&partial);
+    fs::create_dir_all(&partial)
+        .map_err(|e| format!("couldn't create {}: {}", partial.display(), e))?;
+    let result = download(version, &partial).and_then(|()| {
+        fs::rename(&partial, &dir).map_err(|e| format!("couldn't create {}: {}", dir.display(), e))
+    });
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&partial);
+    }
+    result.map(|()| dir)
+}
+
+/// Unpacks the error index from the release's rust-docs component into
+/// `dir`, converting each explanation to markdown.
+fn download(version: &str, dir: &Path) -> Result<(), String> {
+    let manifest = curl(&format!("{}/channel-rust-{}.toml", DIST, version))
+        .map_err(|e| format!("couldn't find Rust {}: {}", version, e))?;
+    let manifest: toml::Value = toml::from_str(&String::from_utf8_lossy(&manifest))
+        .map_err(|e| format!("couldn't read Rust {}'s manifest: {}", version, e))?;
+    let docs = &manifest["pkg"]["rust-docs"]["target"][DOCS_TARGET];
+    let url = docs
+        .get("xz_url")
+        .and_then(toml::Value::as_str)
+        .ok_or_else(|| format!("Rust {} has no documentation to download", version))?;
+
+    // The error index is a book of a page per code since 1.68, and one long
+    // page before that. tar complains about whichever isn't there
+    let html = format!(
+        "rust-docs-{}-{}/rust-docs/share/doc/rust/html",
+        manifest["pkg"]["rust-docs"]["version"]
+            .as_str()
+            .and_then(|version| version.split(' ').next())
+            .unwrap_or(version),
+        DOCS_TARGET
+    );
+    let mut curl = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location", url])
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("couldn't run curl: {}", e))?;
+    let unpacked = Command::new("tar")
+        .arg("-xJf")
+        .arg("-")
+        .arg("-C")
+        .arg(dir)
+        .arg(format!("{}/error_codes", html))
+        .arg(format!("{}/error-index.html", html))
+        .stdin(curl.stdout.take().expect("curl's stdout is piped"))
+        .stderr(Stdio::null())
+        .status();
+    curl.wait()
+        .map_err(|e| format!("couldn't run curl: {}", e))?;
+    unpacked.map_err(|e| format!("couldn't run tar: {}", e))?;
+
+    let unpacked = dir.join(&html);
+    let mut found = 0;
+    let codes = Regex::new(r#"<h2 id="(E\d{4})""#).unwrap();
+    if let Ok(pages) = fs::read_dir(unpacked.join("error_codes")) {
+        for page in pages.flatten() {
+            let name = page.file_name().to_string_lossy().into_owned();
+            let code = match name.strip_suffix(".html") {
+                Some(code) if code.starts_with('E') => code.to_string(),
+                _ => continue,
+            };
+            let page = fs::read_to_string(page.path()).unwrap_or_default();
+            if let Some(body) =
+                html::section(&page, "h1", &format!("error-code-{}", code.to_lowercase()))
+            {
+                let url = format!("https://doc.rust-lang.org/{}/error_codes/{}", version, name);
+                store(dir, &code, &html::to_markdown(body, &url))?;
+                found += 1;
+            }
+        }
+    } else if let Ok(page) = fs::read_to_string(unpacked.join("error-index.html")) {
+        let url = format!("https://doc.rust-lang.org/{}/error-index.html", version);
+        for code in codes.captures_iter(&page) {
+            if let Some(body) = html::section(&page, "h2", &code[1]) {
+                store(dir, &code[1], &html::to_markdown(body, &url))?;
+                found += 1;
+            }
+        }
+    }
+    let _ = fs::remove_dir_all(dir.join(html.split('/').next().unwrap_or_default()));
+    if found == 0 {
+        return Err(format!(
+            "couldn't find an error index in Rust {}'s documentation",
+            version
+        ));
+    }
+    Ok(())
+}
+
+/// Keeps a code's explanation, if it has one: the one-page index lists
+/// codes without one too.
+fn store(dir: &Path, code: &str, explanation: &str) -> Result<(), String> {
+    if explanation.trim().is_empty() {
+        return Ok(());
+    }
+    let path = dir.join(format!("{}.md", code));
+    fs::write(&path, explanation).map_err(|e| format!("couldn't write {}: {}", path.display(), e))
+}
+
+/// The whole of what's at `url`.
+fn curl(url: &str) -> Result<Vec<u8>, String> {
+    let output = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location", url])
+        .output()
+        .map_err(|e| format!("couldn't run curl: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.trim().trim_start_matches("curl: ").to_string());
+    }
+    Ok(output.stdout)
+}
+
+/// A code's entry in a downloaded release's index, as `index::probe` would
+/// find it. Codes without an explanation aren't documented at all, so
+/// they're missing like codes that don't exist.
+pub fn probe(dir: &Path, code: &str) -> Option<IndexEntry> {
+    let explanation = fs::read_to_string(dir.join(format!("{}.md", code))).ok()?;
+    Some(IndexEntry {
+        code: code.to_string(),
+        title: index::title_of(&explanation),
+        has_explanation: true,
+        explanation,
+    })
+}
@@ -0,0 +1,230 @@
+use regex::Regex;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Finds every error code in compiler output, like `error[E0502]` or the
+/// codes listed after "Some errors have detailed explanations", each once in
+/// order of first appearance.
+pub fn codes_in_message(message: &str) -> Vec<String> {
+    let code_pattern = Regex::new(r"\bE\d{4}\b").unwrap();
+    let mut found: Vec<String> = Vec::new();
+    for code in code_pattern.find_iter(message) {
+        if !found.iter().any(|c| c == code.as_str()) {
+            found.push(code.as_str().to_string());
+        }
+    }
+    found
+}
+
+/// Finds the errors in a human-readable build log, like a CI log: each
+/// distinct code marked `error[E0308]`, in order of first appearance, with
+/// how many times it came up and the `-->` location of its first error.
+pub fn errors_in_log(log: &str) -> Vec<(String, usize, Option<String>)> {
+    let escapes = Regex::new(r"\x1B\[[0-9;]*m").unwrap();
+    let log = escapes.replace_all(log, "");
+    let error_pattern = Regex::new(r"\berror\[(E\d{4})\]").unwrap();
+    let location_pattern = Regex::new(r"^\s*--> (\S+)").unwrap();
+    let mut found: Vec<(String, usize, Option<String>)> = Vec::new();
+    // The error the next `-->` line belongs to, if it's a first occurrence
+    let mut locating = None;
+    for line in log.lines() {
+        if let Some(captures) = error_pattern.captures(line) {
+            let code = &captures[1];
+            locating = match found.iter().position(|(c, _, _)| c == code) {
+                Some(i) => {
+                    found[i].1 += 1;
+                    None
+                }
+                None => {
+                    found.push((code.to_string(), 1, None));
+                    Some(found.len() - 1)
+                }
+            };
+        } else if let Some(captures) = location_pattern.captures(line) {
+            if let Some(i) = locating.take() {
+                found[i].2 = Some(captures[1].to_string());
+            }
+        } else if line.starts_with("error") || line.starts_with("warning") {
+            locating = None;
+        }
+    }
+    found
+}
+
+/// Finds error codes mentioned in the comments of a Rust source file,
+/// returning each distinct code in order of first appearance along with
+/// every line it appears on.
+pub fn codes_in_comments(path: &Path) -> io::Result<Vec<(String, Vec<usize>)>> {
+    let source = fs::read_to_string(path)?;
+    let code_pattern = Regex::new(r"\bE\d{4}\b").unwrap();
+    let mut found: Vec<(String, Vec<usize>)> = Vec::new();
+
+    for (i, comment) in comments_by_line(&source).iter().enumerate() {
+        for code in code_pattern.find_iter(comment) {
+            let code = code.as_str();
+            match found.iter_mut().find(|(c, _)| c == code) {
+                Some((_, lines)) => {
+                    if lines.last() != Some(&(i + 1)) {
+                        lines.push(i + 1);
+                    }
+                }
+                None => found.push((code.to_string(), vec![i + 1])),
+            }
+        }
+    }
+
+    Ok(found)
+}
+
+/// The text of the comments on each line of Rust source. String and char
+/// literals are skipped over, since a `//` in one, as in a URL, doesn't
+/// start a comment.
+fn comments_by_line(source: &str) -> Vec<String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut lines = vec![String::new()];
+    let mut block_depth = 0;
+    let mut line_comment = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c == '\n' {
+            lines.push(String::new());
+            line_comment = false;
+            i += 1;
+        } else if line_comment {
+            lines.last_mut().unwrap().push(c);
+            i += 1;
+        } else if block_depth > 0 {
+            match (c, next) {
+                ('*', Some('/')) => {
+                    block_depth -= 1;
+                    i += 2;
+                }
+                ('/', Some('*')) => {
+                    block_depth += 1;
+                    i += 2;
+                }
+                _ => {
+                    lines.last_mut().unwrap().push(c);
+                    i += 1;
+                }
+            }
+        } else {
+            let end = match (c, next) {
+                ('/', Some('/')) => {
+                    line_comment = true;
+                    i + 2
+                }
+                ('/', Some('*')) => {
+                    block_depth = 1;
+                    i + 2
+                }
+                _ => literal_end(&chars, i).unwrap_or(i + 1),
+            };
+            // Strings can run over several lines
+            let newlines = chars[i..end.min(chars.len())]
+                .iter()
+                .filter(|&&c| c == '\n')
+                .count();
+            lines.extend(std::iter::repeat_n(String::new(), newlines));
+            i = end;
+        }
+    }
+    lines
+}
+
+/// Where the string or char literal starting at `chars[i]` ends, if one
+/// does. A quote that starts no char literal is a lifetime's.
+fn literal_end(chars: &[char], i: usize) -> Option<usize> {
+    let is_ident = |k: usize| chars[k].is_alphanumeric() || chars[k] == '_';
+    match chars[i] {
+        '"' => {
+            let mut j = i + 1;
+            while j < chars.len() {
+                match chars[j] {
+                    '\\' => j += 2,
+                    '"' => return Some(j + 1),
+                    _ => j += 1,
+                }
+            }
+            Some(j)
+        }
+        '\'' => match (chars.get(i + 1), chars.get(i + 2)) {
+            (Some('\\'), _) => {
+                let close = chars[i + 2..].iter().skip(1).position(|&c| c == '\'')?;
+                Some(i + 3 + close + 1)
+            }
+            (Some(_), Some('\'')) => Some(i + 3),
+            _ => None,
+        },
+        // A raw string, like r"..." or br#"..."#, but not an identifier
+        // that ends in r
+        'r' if i == 0
+            || !is_ident(i - 1)
+            || (chars[i - 1] == 'b' && (i < 2 || !is_ident(i - 2))) =>
+        {
+            let hashes = chars[i + 1..].iter().take_while(|&&c| c == '#').count();
+            if chars.get(i + 1 + hashes) != Some(&'"') {
+                return None;
+            }
+            let mut j = i + 2 + hashes;
+            while j < chars.len() {
+                let closes = chars[j] == '"'
+                    && chars[j + 1..]
+                        .iter()
+                        .take(hashes)
+                        .filter(|&&c| c == '#')
+                        .count()
+                        == hashes;
+                if closes {
+                    return Some(j + 1 + hashes);
+                }
+                j += 1;
+            }
+            Some(j)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_comment_markers_in_literals() {
+        let source = "let url = \"https://example.com/E0001\"; // see E0308\n\
+                      let raw = r#\"a \"/* E0002 */\" b\"#;\n\
+                      let c = '\"'; let d = '/'; fn f<'a>(x: &'a str) {} /* E0277 */\n\
+                      let s = \"runs\n\
+                      // E0003 over lines\";\n\
+                      /* nested /* E0499 */ still E0502 */ after\n";
+        let comments = comments_by_line(source);
+        let codes = codes_in_message(&comments.join("\n"));
+        assert_eq!(codes, ["E0308", "E0277", "E0499", "E0502"]);
+        assert_eq!(comments.len(), 7);
+        assert!(comments[4].is_empty());
+    }
+
+    #[test]
+    fn lists_comment_codes_in_order_with_lines() {
+        let path = std::env::temp_dir().join(format!("scan-{}.rs", std::process::id()));
+        let source = "// E0502, then E0308\n\
+                      fn main() {} // E0308 and E0308 again\n\
+                      /* E0502\n\
+                      E0277 */\n";
+        fs::write(&path, source).unwrap();
+        let found = codes_in_comments(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            found.unwrap(),
+            [
+                ("E0502".to_string(), vec![1, 3]),
+                ("E0308".to_string(), vec![1, 2]),
+                ("E0277".to_string(), vec![4]),
+            ]
+        );
+    }
+}
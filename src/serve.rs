@@ -0,0 +1,57 @@
+//! `--serve`: explain codes read from stdin one per line, for editors that
+//! would otherwise spawn a new process (and reload every syntax and theme)
+//! for each lookup.
+
+use std::io::{self, BufRead, Write};
+
+use cargo_explain::{render, render_json, RenderOptions};
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+
+use crate::{fetch_explanation, normalize_code, warn, Format};
+
+/// Marks the end of each response. It can't appear in rendered output, since
+/// control characters in explanations are shown in caret notation.
+pub const DELIMITER: u8 = b'\0';
+
+/// Answers requests until stdin closes. Each response is the rendered
+/// explanation followed by `DELIMITER`; codes rustc doesn't know get an
+/// empty response and a warning on stderr.
+pub fn serve(
+    ps: &SyntaxSet,
+    ts: &ThemeSet,
+    options: &RenderOptions,
+    format: Format,
+    json_render: bool,
+    locale: Option<&str>,
+) -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    for line in stdin.lock().lines() {
+        let line = line?;
+        let code = line.trim();
+        if code.is_empty() {
+            continue;
+        }
+        let code = normalize_code(code).unwrap_or_else(|| code.to_string());
+        let response = match fetch_explanation(&code, locale) {
+            Ok(Some(input)) => match format {
+                Format::Terminal => render(&input, ps, ts, options, None),
+                Format::Json => render_json(&input, ps, ts, options, json_render).to_string(),
+            },
+            Ok(None) => {
+                warn(&format!("{} is not a valid error code", code));
+                String::new()
+            }
+            Err(e) => {
+                warn(&format!("couldn't explain {}: {}", code, e));
+                String::new()
+            }
+        };
+        let mut out = stdout.lock();
+        out.write_all(response.as_bytes())?;
+        out.write_all(&[DELIMITER])?;
+        out.flush()?;
+    }
+    Ok(())
+}
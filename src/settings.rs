@@ -0,0 +1,216 @@
+//! Where each setting comes from. Later layers win:
+//!
+//! 1. built-in defaults
+//! 2. the config file
+//! 3. `NO_COLOR` and `CLICOLOR_FORCE`, for color alone
+//! 4. a `--profile`
+//! 5. `CARGO_EXPLAIN_*` environment variables, which clap reads for each
+//!    flag that has one
+//! 6. flags
+
+use cargo_explain::{CodeBackground, InlineCode, Spacing};
+
+use crate::cli::Cli;
+use crate::{
+    config, error, term, warn, ColorWhen, Format, GroupBy, Hyperlinks, Options, TrailingNewline,
+    DEFAULT_INDENT, DOCS_BASE,
+};
+
+/// Resolves the settings for a run from the parsed command line and
+/// everything beneath it.
+pub fn resolve(cli: Cli, command_name: String) -> Options {
+    let config = config::load().unwrap_or_else(|e| error(&e));
+    let mut options = Options {
+        command_name,
+        err_names: Vec::new(),
+        docs_base: DOCS_BASE.to_string(),
+        theme: config.theme,
+        copy_url: false,
+        open: false,
+        merge_code: false,
+        index: false,
+        code_bg: CodeBackground::Theme,
+        inline_code: InlineCode::Tint,
+        pager: config.pager,
+        no_pager: config.no_pager,
+        force: false,
+        prompt: None,
+        indent: DEFAULT_INDENT,
+        spacing: Spacing::default(),
+        match_message: None,
+        from_message: None,
+        log: None,
+        future_incompat: false,
+        no_trailing_reset: false,
+        toc: false,
+        no_toc: false,
+        no_related: false,
+        numbered: false,
+        group_by: GroupBy::None,
+        pad_code: false,
+        explain_all_in: None,
+        max_blocks: None,
+        full: false,
+        mono: false,
+        ascii: false,
+        styles: config.styles,
+        color: if term::no_color() {
+            ColorWhen::Never
+        } else if term::force_color() {
+            ColorWhen::Always
+        } else {
+            config.color.unwrap_or(ColorWhen::Auto)
+        },
+        format: Format::Terminal,
+        json_render: false,
+        columns: false,
+        serve: false,
+        pipe: false,
+        watch: false,
+        hyperlinks: Hyperlinks::Auto,
+        locale: None,
+        diff_themes: None,
+        diff_toolchains: None,
+        trailing_newline: TrailingNewline::Auto,
+        pin_title: false,
+        show_hidden: false,
+        header_rules: false,
+        hyphenate: false,
+        line_numbers: false,
+        diff_examples: false,
+        frame_code: false,
+        width: config.width,
+        #[cfg(feature = "bat")]
+        use_bat: false,
+    };
+
+    // A profile only sets defaults, so apply it before any flag gets a
+    // chance to override it.
+    if let Some(profile) = cli.profile {
+        profile.apply(&mut options);
+    }
+
+    options.err_names = cli.explain.into_iter().chain(cli.codes).collect();
+    options.match_message = cli.match_message;
+    options.from_message = cli.from_message;
+    options.log = cli.log;
+    options.future_incompat = cli.future_incompat;
+    options.explain_all_in = cli.explain_all_in;
+    options.force = cli.force;
+    if cli.prompt {
+        options.prompt = Some(true);
+    } else if cli.no_prompt {
+        options.prompt = Some(false);
+    }
+    options.index = cli.index;
+    options.serve = cli.serve;
+    options.pipe = cli.pipe;
+    options.watch = cli.watch;
+    if let Some(theme) = cli.theme {
+        options.theme = Some(theme);
+    }
+    if let Some(toolchains) = cli.diff {
+        if let [a, b] = toolchains.as_slice() {
+            options.diff_toolchains = Some((a.clone(), b.clone()));
+        }
+    }
+    if let Some(themes) = cli.diff_themes {
+        if let [a, b] = themes.as_slice() {
+            options.diff_themes = Some((a.clone(), b.clone()));
+        }
+    }
+    if let Some(color) = cli.color {
+        options.color = color;
+    }
+    // The `--no-` flags win, to switch off what a profile or the
+    // environment turned on
+    if cli.no_mono {
+        options.mono = false;
+    } else if cli.mono {
+        options.mono = true;
+    }
+    options.ascii = cli.ascii;
+    if let Some(value) = cli.code_bg {
+        options.code_bg = CodeBackground::parse(&value).unwrap_or_else(|| {
+            warn(&format!(
+                "invalid --code-bg color {:?}, using the theme background",
+                value
+            ));
+            CodeBackground::Theme
+        });
+    }
+    if let Some(inline_code) = cli.inline_code {
+        options.inline_code = inline_code;
+    }
+    if let Some(width) = cli.width {
+        options.width = Some(width);
+    }
+    if let Some(indent) = cli.indent {
+        options.indent = indent;
+    }
+    if let Some(spacing) = cli.spacing {
+        options.spacing = spacing;
+    }
+    options.hyphenate = cli.hyphenate;
+    if cli.no_merge_code {
+        options.merge_code = false;
+    } else if cli.merge_code {
+        options.merge_code = true;
+    }
+    if cli.no_pad_code {
+        options.pad_code = false;
+    } else if cli.pad_code {
+        options.pad_code = true;
+    }
+    options.line_numbers = cli.line_numbers;
+    options.frame_code = cli.frame_code;
+    options.diff_examples = cli.diff_examples;
+    options.show_hidden = cli.show_hidden;
+    #[cfg(feature = "bat")]
+    {
+        options.use_bat = cli.use_bat;
+    }
+    options.toc |= cli.toc;
+    options.no_toc = cli.no_toc;
+    options.numbered = cli.numbered;
+    options.header_rules = cli.header_rules;
+    options.no_related = cli.no_related;
+    if cli.no_columns {
+        options.columns = false;
+    } else if cli.columns {
+        options.columns = true;
+    }
+    options.max_blocks = cli.max_blocks;
+    options.full = cli.full;
+    if let Some(group_by) = cli.group_by {
+        options.group_by = group_by;
+    }
+    if let Some(hyperlinks) = cli.hyperlinks {
+        options.hyperlinks = hyperlinks;
+    }
+    options.pin_title = cli.pin_title;
+    if let Some(format) = cli.format {
+        options.format = format;
+    }
+    if cli.json_render {
+        options.format = Format::Json;
+        options.json_render = true;
+    }
+    options.locale = cli.lang_locale;
+    if let Some(pager) = cli.pager {
+        options.pager = Some(pager);
+        options.no_pager = false;
+    }
+    options.no_pager |= cli.no_pager;
+    if let Some(trailing_newline) = cli.trailing_newline {
+        options.trailing_newline = trailing_newline;
+    }
+    options.no_trailing_reset = cli.no_trailing_reset;
+    options.copy_url = cli.copy_url;
+    options.open = cli.open;
+    if let Some(docs_base) = cli.docs_base {
+        options.docs_base = docs_base;
+    }
+
+    options
+}
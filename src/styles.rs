@@ -0,0 +1,135 @@
+//! User styles for prose: headers, emphasis, quotes, and inline code.
+
+use anstyle::{Ansi256Color, AnsiColor, Color, RgbColor, Style};
+use std::fmt::Display;
+
+use crate::{ansi16, ansi256, CodeBackground, ColorDepth, Renderer};
+
+/// Styles that replace the built-in look of parts of prose. Anything left
+/// as `None` looks as it always has.
+#[derive(Clone, Copy, Default)]
+pub struct Styles {
+    /// Headers by level, `#` first.
+    pub headers: [Option<Style>; 6],
+    pub emphasis: Option<Style>,
+    pub strong: Option<Style>,
+    pub blockquote_bar: Option<Style>,
+    /// The background behind inline code, in place of the code background.
+    pub inline_code_bg: Option<CodeBackground>,
+}
+
+impl Styles {
+    /// Sets the style named by a config key, like `h2` or `emphasis`.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let header = key
+            .strip_prefix('h')
+            .and_then(|level| level.parse::<usize>().ok())
+            .filter(|level| (1..=6).contains(level));
+        match (key, header) {
+            (_, Some(level)) => self.headers[level - 1] = Some(parse_style(value)?),
+            ("emphasis", _) => self.emphasis = Some(parse_style(value)?),
+            ("strong", _) => self.strong = Some(parse_style(value)?),
+            ("blockquote_bar", _) => self.blockquote_bar = Some(parse_style(value)?),
+            ("inline_code_bg", _) => {
+                let bg = CodeBackground::parse(value).ok_or_else(|| {
+                    format!(
+                        "invalid inline_code_bg {:?}, expected #rrggbb or none",
+                        value
+                    )
+                })?;
+                self.inline_code_bg = Some(bg);
+            }
+            _ => return Err(format!("unknown style {:?}", key)),
+        }
+        Ok(())
+    }
+}
+
+/// Drawing text in a style, ending with a reset so it doesn't leak into
+/// whatever follows. A plain style draws the text as it is.
+pub trait Paint {
+    fn paint(self, text: impl Display) -> String;
+}
+
+impl Paint for Style {
+    fn paint(self, text: impl Display) -> String {
+        format!("{}{}{}", self.render(), text, self.render_reset())
+    }
+}
+
+/// Parses a style like `bold purple` or `italic #ff8800 on black`: any of
+/// `bold`, `dimmed`, `italic`, `underline`, `reverse` and `strikethrough`,
+/// a color, and `on` a background color. Colors are names, palette numbers
+/// up to 255, or `#rrggbb`.
+pub fn parse_style(spec: &str) -> Result<Style, String> {
+    let mut style = Style::new();
+    let mut words = spec.split_whitespace();
+    while let Some(word) = words.next() {
+        style = match word {
+            "bold" => style.bold(),
+            "dimmed" | "dim" => style.dimmed(),
+            "italic" => style.italic(),
+            "underline" => style.underline(),
+            "reverse" => style.invert(),
+            "strikethrough" => style.strikethrough(),
+            "on" => match words.next() {
+                Some(color) => style.bg_color(Some(parse_color(color)?)),
+                None => return Err(format!("missing background color in {:?}", spec)),
+            },
+            color => style.fg_color(Some(parse_color(color)?)),
+        };
+    }
+    Ok(style)
+}
+
+fn parse_color(word: &str) -> Result<Color, String> {
+    let color = match word {
+        "black" => AnsiColor::Black,
+        "red" => AnsiColor::Red,
+        "green" => AnsiColor::Green,
+        "yellow" => AnsiColor::Yellow,
+        "blue" => AnsiColor::Blue,
+        "purple" | "magenta" => AnsiColor::Magenta,
+        "cyan" => AnsiColor::Cyan,
+        "white" => AnsiColor::White,
+        hex if hex.starts_with('#') => match CodeBackground::parse(hex) {
+            Some(CodeBackground::Custom(c)) => return Ok(RgbColor(c.r, c.g, c.b).into()),
+            _ => return Err(format!("invalid color {:?}, expected #rrggbb", word)),
+        },
+        number => match number.parse() {
+            Ok(n) => return Ok(Ansi256Color(n).into()),
+            Err(_) => return Err(format!("unknown style or color {:?}", word)),
+        },
+    };
+    Ok(color.into())
+}
+
+/// A user style as the terminal can show it: without color when color is
+/// off, and with RGB colors brought down to the palette it has.
+pub(crate) fn fit(style: Style, r: &Renderer) -> Style {
+    if r.options.mono {
+        return style.fg_color(None).bg_color(None);
+    }
+    let fit_color = |color: Color| match (color, r.options.color_depth) {
+        (Color::Rgb(RgbColor(red, green, blue)), ColorDepth::Ansi256) => {
+            Ansi256Color(ansi256((red, green, blue))).into()
+        }
+        (Color::Rgb(RgbColor(red, green, blue)), ColorDepth::Ansi16) => {
+            let basic = Ansi256Color(ansi16((red, green, blue)));
+            basic.into_ansi().map_or(basic.into(), Color::Ansi)
+        }
+        (color, _) => color,
+    };
+    style
+        .fg_color(style.get_fg_color().map(fit_color))
+        .bg_color(style.get_bg_color().map(fit_color))
+}
+
+/// `top` drawn over `base`: its colors where it has them, and the font
+/// styles of both.
+pub(crate) fn overlay(base: Style, top: Style) -> Style {
+    Style::new()
+        .fg_color(top.get_fg_color().or(base.get_fg_color()))
+        .bg_color(top.get_bg_color().or(base.get_bg_color()))
+        .effects(base.get_effects() | top.get_effects())
+}
@@ -0,0 +1,145 @@
+//! Pipe tables, drawn with box-drawing borders.
+
+use anstyle::Style;
+
+use crate::{render_spans, Paint, Renderer, Span, ANSI_RESET};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub header: Vec<Vec<Span>>,
+    pub align: Vec<Align>,
+    pub rows: Vec<Vec<Vec<Span>>>,
+}
+
+/// Renders a cell's spans, to be drawn in `style`.
+fn render_cell(spans: &[Span], r: &Renderer, style: Style) -> String {
+    render_spans(spans, r, style).trim_end().to_string()
+}
+
+/// Shrinks the widest columns until the table fits in `available` columns,
+/// but never narrower than their longest word.
+fn fit_widths(widths: &mut [usize], min: &[usize], available: usize) {
+    while widths.iter().sum::<usize>() > available {
+        let widest = (0..widths.len())
+            .filter(|&i| widths[i] > min[i])
+            .max_by_key(|&i| widths[i]);
+        match widest {
+            Some(i) => widths[i] -= 1,
+            None => break,
+        }
+    }
+}
+
+fn pad(text: &str, width: usize, align: Align) -> String {
+    let missing = width.saturating_sub(textwrap::core::display_width(text));
+    let (left, right) = match align {
+        Align::Left => (0, missing),
+        Align::Right => (missing, 0),
+        Align::Center => (missing / 2, missing - missing / 2),
+    };
+    format!("{}{}{}", " ".repeat(left), text, " ".repeat(right))
+}
+
+/// Draws a table with box-drawing borders, wrapping cells so the table fits
+/// the wrap width where it can. Header cells are bold.
+pub fn render_table(table: &Table, r: &Renderer) -> String {
+    let columns = table
+        .rows
+        .iter()
+        .map(Vec::len)
+        .chain([table.header.len()])
+        .max()
+        .unwrap_or_default();
+    let render_row = |row: &[Vec<Span>], style: Style| -> Vec<String> {
+        (0..columns)
+            .map(|i| render_cell(row.get(i).map_or(&[], Vec::as_slice), r, style))
+            .collect()
+    };
+    let header = render_row(&table.header, Style::new().bold());
+    let rows: Vec<Vec<String>> = table
+        .rows
+        .iter()
+        .map(|row| render_row(row, Style::new()))
+        .collect();
+
+    let all_rows = || std::iter::once(&header).chain(rows.iter());
+    let mut widths = vec![1; columns];
+    let mut min = vec![1; columns];
+    for row in all_rows() {
+        for (i, cell) in row.iter().enumerate() {
+            let longest_line = cell.lines().map(textwrap::core::display_width).max();
+            widths[i] = widths[i].max(longest_line.unwrap_or_default());
+            let longest_word = cell
+                .split([' ', '\n'])
+                .map(textwrap::core::display_width)
+                .max();
+            min[i] = min[i].max(longest_word.unwrap_or_default());
+        }
+    }
+    // Each column has a space of padding either side, and a border after
+    fit_widths(
+        &mut widths,
+        &min,
+        r.wrap_width.saturating_sub(3 * columns + 1),
+    );
+
+    let glyphs = r.glyphs();
+    let border = Style::new().dimmed();
+    let rule = |[left, middle, right]: [&str; 3]| {
+        let segments: Vec<String> = widths.iter().map(|w| glyphs.rule.repeat(w + 2)).collect();
+        border.paint(format!("{}{}{}", left, segments.join(middle), right))
+    };
+    let draw_row = |row: &[String], style: Style| -> Vec<String> {
+        let wrapped: Vec<Vec<String>> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| {
+                let options = textwrap::Options::new(width)
+                    .word_separator(textwrap::WordSeparator::AsciiSpace)
+                    .word_splitter(textwrap::WordSplitter::NoHyphenation);
+                // Style each line on its own, so the border in between
+                // doesn't pick it up
+                textwrap::wrap(cell, options)
+                    .into_iter()
+                    .map(|line| style.paint(line))
+                    .collect()
+            })
+            .collect();
+        let height = wrapped.iter().map(Vec::len).max().unwrap_or(1);
+        let bar = border.paint(glyphs.bar);
+        (0..height)
+            .map(|line| {
+                let cells: Vec<String> = wrapped
+                    .iter()
+                    .zip(&widths)
+                    .enumerate()
+                    .map(|(i, (cell, &width))| {
+                        // Rows can have more cells than the delimiter row
+                        // gave alignments for
+                        let align = table.align.get(i).copied().unwrap_or(Align::Left);
+                        let text = cell.get(line).map_or("", String::as_str);
+                        format!(" {}{} ", pad(text, width, align), ANSI_RESET)
+                    })
+                    .collect();
+                format!("{}{}{}", bar, cells.join(&bar), bar)
+            })
+            .collect()
+    };
+
+    let [top, under_header, bottom] = glyphs.table_joints;
+    let mut lines = vec![rule(top)];
+    lines.extend(draw_row(&header, Style::new().bold()));
+    lines.push(rule(under_header));
+    for row in &rows {
+        lines.extend(draw_row(row, Style::new()));
+    }
+    lines.push(rule(bottom));
+    lines.join("\n")
+}
@@ -0,0 +1,244 @@
+//! Queries about the terminal stdout is attached to.
+
+use cargo_explain::ColorDepth;
+use std::env;
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// The width of the terminal in columns, from stdout if it's a terminal or
+/// else from `$COLUMNS`.
+pub fn width() -> Option<usize> {
+    tty_size()
+        .map(|(columns, _)| columns)
+        .or_else(|| env_size("COLUMNS"))
+}
+
+/// The height of the terminal in lines, from stdout if it's a terminal or
+/// else from `$LINES`.
+pub fn height() -> Option<usize> {
+    tty_size()
+        .map(|(_, lines)| lines)
+        .or_else(|| env_size("LINES"))
+}
+
+fn env_size(name: &str) -> Option<usize> {
+    env::var(name).ok()?.parse().ok().filter(|&n| n > 0)
+}
+
+/// The terminal's columns and lines.
+#[cfg(unix)]
+fn tty_size() -> Option<(usize, usize)> {
+    // SAFETY: TIOCGWINSZ only writes into the winsize struct we pass it.
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    if result == 0 && size.ws_col > 0 && size.ws_row > 0 {
+        Some((size.ws_col as usize, size.ws_row as usize))
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn tty_size() -> Option<(usize, usize)> {
+    None
+}
+
+/// Whether `$NO_COLOR` asks for output without color.
+pub fn no_color() -> bool {
+    anstyle_query::no_color()
+}
+
+/// Whether `$CLICOLOR_FORCE` asks for styled output even when stdout isn't
+/// a terminal. `$NO_COLOR` wins over it.
+pub fn force_color() -> bool {
+    !no_color() && anstyle_query::clicolor_force()
+}
+
+/// How many colors the terminal can show. `$COLORTERM` says whether it has
+/// true color, then `$TERM`'s name, and only when that's no help does
+/// terminfo say how many colors it has. Asked once per run.
+pub fn color_depth() -> ColorDepth {
+    static DEPTH: OnceLock<ColorDepth> = OnceLock::new();
+    *DEPTH.get_or_init(query_color_depth)
+}
+
+fn query_color_depth() -> ColorDepth {
+    if anstyle_query::truecolor() {
+        return ColorDepth::TrueColor;
+    }
+    // Windows Terminal has always had true color, but doesn't say so
+    if env::var_os("WT_SESSION").is_some() {
+        return ColorDepth::TrueColor;
+    }
+    let term = env::var("TERM").unwrap_or_default();
+    if term.ends_with("-direct") {
+        return ColorDepth::TrueColor;
+    }
+    if term.contains("256color") {
+        return ColorDepth::Ansi256;
+    }
+    if term.is_empty() || term == "dumb" {
+        return ColorDepth::Ansi16;
+    }
+    let colors = Command::new("tput")
+        .arg("colors")
+        .output()
+        .ok()
+        .and_then(|output| {
+            String::from_utf8(output.stdout)
+                .ok()?
+                .trim()
+                .parse::<u32>()
+                .ok()
+        });
+    match colors {
+        Some(colors) if colors >= 1 << 24 => ColorDepth::TrueColor,
+        Some(colors) if colors >= 256 => ColorDepth::Ansi256,
+        _ => ColorDepth::Ansi16,
+    }
+}
+
+/// Terminals known to understand OSC 8 hyperlinks, by `$TERM_PROGRAM`.
+const HYPERLINK_PROGRAMS: &[&str] = &["iTerm.app", "WezTerm", "vscode", "Hyper", "ghostty"];
+
+/// Terminals known to understand OSC 8 hyperlinks, by `$TERM`.
+const HYPERLINK_TERMS: &[&str] = &[
+    "xterm-kitty",
+    "alacritty",
+    "foot",
+    "wezterm",
+    "xterm-ghostty",
+];
+
+/// Whether the terminal is known to support OSC 8 hyperlinks. Terminals
+/// that aren't recognized are assumed not to, since unsupported escapes
+/// can show up as garbage.
+pub fn supports_hyperlinks() -> bool {
+    let var = |name| env::var(name).unwrap_or_default();
+    HYPERLINK_PROGRAMS.contains(&var("TERM_PROGRAM").as_str())
+        || HYPERLINK_TERMS.contains(&var("TERM").as_str())
+        || env::var_os("WT_SESSION").is_some()
+        || env::var_os("KONSOLE_VERSION").is_some()
+        // GNOME Terminal and other VTE terminals since 0.50
+        || var("VTE_VERSION").parse::<u32>().is_ok_and(|v| v >= 5000)
+}
+
+/// Whether the terminal draws dark text on a light background or the
+/// other way around.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Background {
+    Light,
+    Dark,
+}
+
+/// How long to wait for the terminal to say what its background color is.
+/// Terminals that don't understand the question never answer.
+#[cfg(unix)]
+const BACKGROUND_QUERY_TIMEOUT_MS: i32 = 100;
+
+/// The terminal's background, asked of the terminal itself with OSC 11, or
+/// failing that guessed from `$COLORFGBG`.
+pub fn background() -> Option<Background> {
+    query_background()
+        .as_deref()
+        .and_then(parse_osc_color)
+        .map(|(r, g, b)| {
+            let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+            if luminance > 0.5 {
+                Background::Light
+            } else {
+                Background::Dark
+            }
+        })
+        .or_else(colorfgbg_background)
+}
+
+/// `$COLORFGBG` is the foreground and background as palette numbers, like
+/// `15;0`. Of the 16 basic colors, white, light gray and the bright ones
+/// other than dark gray make light backgrounds.
+fn colorfgbg_background() -> Option<Background> {
+    let var = env::var("COLORFGBG").ok()?;
+    let background: u8 = var.rsplit(';').next()?.parse().ok()?;
+    match background {
+        7 | 9..=15 => Some(Background::Light),
+        0..=6 | 8 => Some(Background::Dark),
+        _ => None,
+    }
+}
+
+/// Parses an `rgb:rrrr/gggg/bbbb` color reply into fractions of full
+/// brightness. Each channel has one to four hex digits.
+fn parse_osc_color(reply: &str) -> Option<(f64, f64, f64)> {
+    let start = reply.find("rgb:")? + "rgb:".len();
+    let channels = reply[start..].split(['\x07', '\x1B']).next()?;
+    let mut values = channels.split('/').map(|channel| {
+        let digits = channel.get(..channel.len().min(4))?;
+        let value = u32::from_str_radix(digits, 16).ok()?;
+        let max = (1u32 << (4 * digits.len())) - 1;
+        Some(f64::from(value) / f64::from(max))
+    });
+    Some((values.next()??, values.next()??, values.next()??))
+}
+
+/// Asks the controlling terminal for its background color, returning its
+/// raw reply.
+#[cfg(unix)]
+fn query_background() -> Option<String> {
+    use std::fs::OpenOptions;
+    use std::io::{Read, Write};
+    use std::os::unix::io::AsRawFd;
+
+    let mut tty = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .ok()?;
+    let fd = tty.as_raw_fd();
+    // SAFETY: tcgetattr only writes into the termios struct we pass it.
+    let mut original: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut original) } != 0 {
+        return None;
+    }
+    // Turn off line buffering and echo so the reply can be read as it
+    // comes and doesn't show up on screen
+    let mut raw = original;
+    raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } != 0 {
+        return None;
+    }
+
+    let mut reply = Vec::new();
+    if tty
+        .write_all(b"\x1B]11;?\x1B\\")
+        .and_then(|_| tty.flush())
+        .is_ok()
+    {
+        let mut byte = [0u8; 1];
+        loop {
+            let mut poll = libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // SAFETY: poll only reads and writes the one pollfd we pass it.
+            if unsafe { libc::poll(&mut poll, 1, BACKGROUND_QUERY_TIMEOUT_MS) } <= 0 {
+                break;
+            }
+            if tty.read(&mut byte).unwrap_or(0) == 0 {
+                break;
+            }
+            reply.push(byte[0]);
+            // Replies end in BEL or ST, whichever the terminal prefers
+            if reply.ends_with(b"\x07") || reply.ends_with(b"\x1B\\") {
+                break;
+            }
+        }
+    }
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &original) };
+    String::from_utf8(reply).ok()
+}
+
+#[cfg(not(unix))]
+fn query_background() -> Option<String> {
+    None
+}
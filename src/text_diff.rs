@@ -0,0 +1,78 @@
+//! Comparing an explanation's text between two compilers, word by word.
+
+use anstyle::{AnsiColor, Style};
+
+use crate::Paint;
+
+/// Splits text into words and the runs of whitespace between them, so
+/// joining the pieces gives the text back.
+fn words(text: &str) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut in_space = None;
+    for (i, c) in text.char_indices() {
+        let space = c.is_whitespace();
+        if in_space.is_some_and(|in_space| in_space != space) {
+            pieces.push(&text[start..i]);
+            start = i;
+        }
+        in_space = Some(space);
+    }
+    if start < text.len() {
+        pieces.push(&text[start..]);
+    }
+    pieces
+}
+
+/// `after` with the words only in `before` struck through in red and the
+/// words only in `after` underlined in green, going by the longest run of
+/// words they have in common. Without color, only the strikethrough and
+/// underline tell them apart.
+pub fn diff_words(before: &str, after: &str, mono: bool) -> String {
+    let a = words(before);
+    let b = words(after);
+    // common[i][j] is how many words a[i..] and b[j..] have in common
+    let mut common = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            common[i][j] = if a[i] == b[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (removed, added) = if mono {
+        (Style::new().strikethrough(), Style::new().underline())
+    } else {
+        (
+            AnsiColor::Red.on_default().strikethrough(),
+            AnsiColor::Green.on_default().underline(),
+        )
+    };
+    // Whitespace is shown unstyled, so changed line breaks and indents
+    // don't draw lines across the screen
+    let mark = |style: Style, word: &str| {
+        if word.trim().is_empty() {
+            word.to_string()
+        } else {
+            style.paint(word)
+        }
+    };
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            out.push_str(a[i]);
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && common[i + 1][j] >= common[i][j + 1]) {
+            out.push_str(&mark(removed, a[i]));
+            i += 1;
+        } else {
+            out.push_str(&mark(added, b[j]));
+            j += 1;
+        }
+    }
+    out
+}
@@ -0,0 +1,139 @@
+//! Comparing how two themes highlight the same code, for theme authors and
+//! people choosing between themes.
+
+use anstyle::{Ansi256Color, AnsiColor};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{FontStyle, Style, ThemeSet};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+use unicode_width::UnicodeWidthChar;
+
+use crate::{
+    ast, escape_colored, find_theme, glyphs, sanitize, Block, ColorDepth, ListItem, Paint,
+    ANSI_RESET,
+};
+
+/// Every code block in `blocks`, including those nested in quotes and lists.
+fn code_blocks(blocks: &[Block], out: &mut Vec<String>) {
+    for block in blocks {
+        match block {
+            Block::CodeBlock(_, code) => out.push(code.clone()),
+            Block::Blockquote(blocks) => code_blocks(blocks, out),
+            Block::List(list) => {
+                for item in &list.items {
+                    if let ListItem::Paragraph(blocks) = item {
+                        code_blocks(blocks, out);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The parts of a style a theme can disagree about.
+fn look(style: &Style) -> (syntect::highlighting::Color, FontStyle) {
+    (style.foreground, style.font_style)
+}
+
+/// The look of each character of `ranges`.
+fn looks(ranges: &[(Style, &str)]) -> Vec<(syntect::highlighting::Color, FontStyle)> {
+    ranges
+        .iter()
+        .flat_map(|(style, text)| text.chars().map(move |_| look(style)))
+        .collect()
+}
+
+/// Renders the code blocks of an explanation under themes `a` and `b`, line
+/// by line, with `^` marking the tokens the themes color differently. Fails
+/// if either theme doesn't exist.
+pub fn diff_themes(
+    input: &str,
+    ps: &SyntaxSet,
+    ts: &ThemeSet,
+    a: &str,
+    b: &str,
+    depth: ColorDepth,
+    ascii: bool,
+) -> Result<String, String> {
+    let (theme_a, theme_b) = (find_theme(ts, a)?, find_theme(ts, b)?);
+    let syntax = ps.find_syntax_by_extension("rs").unwrap();
+    let label_width = a.chars().count().max(b.chars().count());
+    let gutter = Ansi256Color(8)
+        .on_default()
+        .paint(format!(" {} ", glyphs(ascii).bar));
+
+    let mut blocks = Vec::new();
+    code_blocks(&ast::parse(&sanitize(input)), &mut blocks);
+
+    let mut output = String::new();
+    for (n, code) in blocks.iter().enumerate() {
+        let mut h_a = HighlightLines::new(syntax, theme_a);
+        let mut h_b = HighlightLines::new(syntax, theme_b);
+        let mut body = String::new();
+        let (mut tokens, mut differing) = (0, 0);
+        for line in LinesWithEndings::from(code) {
+            let ranges_a = h_a.highlight(line, ps);
+            let ranges_b = h_b.highlight(line, ps);
+            let (looks_a, looks_b) = (looks(&ranges_a), looks(&ranges_b));
+
+            // A token is a run of characters that looks the same in both
+            // themes; mark the non-blank ones whose looks disagree.
+            let mut markers = String::new();
+            let mut previous = None;
+            for ((c, look_a), look_b) in line
+                .trim_end_matches('\n')
+                .chars()
+                .zip(looks_a)
+                .zip(looks_b)
+            {
+                let differs = look_a != look_b;
+                if !c.is_whitespace() && previous != Some((look_a, look_b)) {
+                    tokens += 1;
+                    differing += differs as usize;
+                }
+                previous = Some((look_a, look_b));
+                // Wide characters take up two columns, and get two markers
+                let marker = if differs && !c.is_whitespace() {
+                    "^"
+                } else {
+                    " "
+                };
+                markers.push_str(&marker.repeat(c.width().unwrap_or(0)));
+            }
+
+            for (name, ranges) in [(a, &ranges_a), (b, &ranges_b)] {
+                let escaped = escape_colored(ranges, false, depth);
+                body.push_str(&format!(
+                    "{:>width$}{}{}{}",
+                    name,
+                    gutter,
+                    escaped.trim_end_matches('\n'),
+                    ANSI_RESET,
+                    width = label_width
+                ));
+                body.push('\n');
+            }
+            if markers.contains('^') {
+                body.push_str(&format!(
+                    "{:width$}{}{}\n",
+                    "",
+                    gutter,
+                    AnsiColor::Yellow.on_default().paint(markers.trim_end()),
+                    width = label_width
+                ));
+            }
+        }
+        output.push_str(&format!(
+            "{}\n{}\n",
+            AnsiColor::Cyan.on_default().bold().paint(format!(
+                "Code block {}: {} of {} tokens differ",
+                n + 1,
+                differing,
+                tokens
+            )),
+            body
+        ));
+    }
+    Ok(output.trim_end().to_string())
+}
@@ -0,0 +1,77 @@
+//! Syntect themes beyond the ones built in, and the theme bat is set up
+//! with.
+
+use crate::config::{config_dir, config_home};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use syntect::highlighting::ThemeSet;
+
+/// bat's config directory, `$BAT_CONFIG_DIR` or `~/.config/bat`, where
+/// `bat cache --build` finds the themes it caches.
+fn bat_config_dir() -> Option<PathBuf> {
+    env::var_os("BAT_CONFIG_DIR")
+        .map(PathBuf::from)
+        .or_else(|| Some(config_home()?.join("bat")))
+}
+
+/// Adds the `.tmTheme` files in bat's themes folder and then the ones in
+/// cargo-explain's own, each named after its file. A theme with the name of
+/// one already loaded replaces it. Returns a message for each file that
+/// couldn't be loaded.
+pub fn add_user_themes(ts: &mut ThemeSet) -> Vec<String> {
+    [bat_config_dir(), config_dir()]
+        .iter()
+        .flatten()
+        .flat_map(|dir| add_themes_in(ts, &dir.join("themes")))
+        .collect()
+}
+
+fn add_themes_in(ts: &mut ThemeSet, dir: &Path) -> Vec<String> {
+    if !dir.is_dir() {
+        return Vec::new();
+    }
+    let paths = match ThemeSet::discover_theme_paths(dir) {
+        Ok(paths) => paths,
+        Err(e) => return vec![format!("couldn't read {}: {}", dir.display(), e)],
+    };
+    let mut problems = Vec::new();
+    for path in paths {
+        let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        match ThemeSet::get_theme(&path) {
+            Ok(theme) => {
+                ts.themes.insert(name, theme);
+            }
+            Err(e) => problems.push(format!("couldn't load theme {}: {}", path.display(), e)),
+        }
+    }
+    problems
+}
+
+/// The theme bat is set to use, from `$BAT_THEME` or a `--theme` line in
+/// bat's config file.
+pub fn bat_theme() -> Option<String> {
+    if let Some(theme) = env::var("BAT_THEME").ok().filter(|theme| !theme.is_empty()) {
+        return Some(theme);
+    }
+    let config = env::var_os("BAT_CONFIG_PATH")
+        .map(PathBuf::from)
+        .or_else(|| Some(bat_config_dir()?.join("config")))?;
+    let config = fs::read_to_string(config).ok()?;
+    // The last setting wins, as it does for bat
+    config.lines().rev().find_map(|line| {
+        let line = line.trim();
+        let value = line
+            .strip_prefix("--theme=")
+            .or_else(|| line.strip_prefix("--theme "))?;
+        Some(
+            value
+                .trim()
+                .trim_matches(|c| c == '"' || c == '\'')
+                .to_string(),
+        )
+    })
+}
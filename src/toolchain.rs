@@ -0,0 +1,325 @@
+//! The rustc explanations are asked of, which isn't always the first one
+//! on the `PATH`: rustup picks a toolchain for each directory.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use crate::index::IndexEntry;
+use crate::{bundled, releases};
+
+/// A rustc other than the one on the `PATH`.
+enum Rustc {
+    /// A rustup toolchain, picked with `--toolchain` or `+name`, or pinned
+    /// by the project.
+    Toolchain(String),
+    /// A rustc binary, picked with `--rustc`.
+    Binary(PathBuf),
+    /// No rustc at all, but the error index bundled at build time.
+    Bundled,
+    /// An earlier release's error index, picked with `--release` and
+    /// downloaded into this directory.
+    Release(String, PathBuf),
+}
+
+static RUSTC: OnceLock<Rustc> = OnceLock::new();
+
+/// Whether `--live` ruled out falling back on the bundled index.
+static LIVE: AtomicBool = AtomicBool::new(false);
+
+/// The `rust-toolchain.toml` or `rust-toolchain` file that picked the
+/// toolchain, when the project's pin is what did.
+static PINNED_BY: OnceLock<PathBuf> = OnceLock::new();
+
+/// Has every later rustc run go through `rustup run <toolchain>`. Errors if
+/// rustup can't run that toolchain.
+pub fn select(toolchain: &str) -> Result<(), String> {
+    check(toolchain)?;
+    RUSTC.get_or_init(|| Rustc::Toolchain(toolchain.to_string()));
+    Ok(())
+}
+
+/// Has every later rustc run through the toolchain the current project
+/// pins, so explanations match what it builds with, when nothing else
+/// picked one. Warns, and leaves the default, if it isn't installed.
+pub fn select_pinned() {
+    // cargo run through rustup passes its toolchain on this way, and it
+    // comes before a project's pin
+    if RUSTC.get().is_some() || env::var_os("RUSTUP_TOOLCHAIN").is_some() {
+        return;
+    }
+    let (toolchain, file) = match pinned() {
+        Some(pinned) => pinned,
+        None => return,
+    };
+    let runs = toolchain_rustc(&toolchain)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    match runs {
+        Ok(status) if status.success() => {
+            RUSTC.get_or_init(|| Rustc::Toolchain(toolchain));
+            PINNED_BY.get_or_init(|| file);
+        }
+        Ok(_) => crate::warn(&format!(
+            "{} pins toolchain {:?}, which isn't installed; try `rustup toolchain install {}`",
+            file.display(),
+            toolchain,
+            toolchain
+        )),
+        // Without rustup, there's only the rustc there is
+        Err(_) => {}
+    }
+}
+
+/// The toolchain the project in the current directory pins, and the file
+/// that pins it, looked for as rustup does: `rust-toolchain` before
+/// `rust-toolchain.toml`, in the nearest directory that has either.
+fn pinned() -> Option<(String, PathBuf)> {
+    let dir = env::current_dir().ok()?;
+    let file = dir.ancestors().find_map(|dir| {
+        ["rust-toolchain", "rust-toolchain.toml"]
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|file| file.is_file())
+    })?;
+    let contents = fs::read_to_string(&file).ok()?;
+    // The old `rust-toolchain` can be just the toolchain's name
+    let toolchain = match contents.parse::<toml::Table>() {
+        Ok(table) => table
+            .get("toolchain")?
+            .get("channel")?
+            .as_str()?
+            .to_string(),
+        Err(_) => contents.trim().to_string(),
+    };
+    Some((toolchain, file)).filter(|(toolchain, _)| !toolchain.is_empty())
+}
+
+/// Errors if rustup can't run `toolchain`, saying how to get it.
+pub fn check(toolchain: &str) -> Result<(), String> {
+    let runs = toolchain_rustc(toolchain)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    match runs {
+        Ok(status) if status.success() => {}
+        Ok(_) => {
+            return Err(format!(
+                "toolchain {:?} isn't installed; try `rustup toolchain install {}`",
+                toolchain, toolchain
+            ))
+        }
+        Err(_) => return Err("picking a toolchain needs rustup, which wasn't found".to_string()),
+    }
+    Ok(())
+}
+
+/// Has every later rustc run use the rustc at `path`, like a local build.
+/// Errors if it can't be run, or can't explain error codes.
+pub fn select_binary(path: &Path) -> Result<(), String> {
+    let explains = Command::new(path)
+        .args(["--explain", "E0001"])
+        .stderr(Stdio::null())
+        .output();
+    match explains {
+        Ok(output) if output.status.success() && !output.stdout.is_empty() => {}
+        Ok(_) => return Err(format!("{} doesn't support --explain", path.display())),
+        Err(e) => return Err(format!("couldn't run {}: {}", path.display(), e)),
+    }
+    RUSTC.get_or_init(|| Rustc::Binary(path.to_path_buf()));
+    Ok(())
+}
+
+/// Never lets explanations fall back on the bundled error index, for
+/// `--live`.
+pub fn stay_live() {
+    LIVE.store(true, Ordering::Relaxed);
+}
+
+/// Has explanations come from the bundled error index from now on, after
+/// running rustc failed with `error`, if that's because there's no rustc on
+/// the `PATH` and nothing else was picked. Warns and returns true if it
+/// does, so the caller can ask the index instead.
+pub fn fall_back_to_bundle(error: &io::Error) -> bool {
+    if error.kind() != io::ErrorKind::NotFound {
+        return false;
+    }
+    match use_bundle() {
+        Some(version) => {
+            crate::warn(&format!(
+                "rustc wasn't found, so explanations come from the index bundled with \
+                 cargo-explain, from {}",
+                version
+            ));
+            true
+        }
+        None => false,
+    }
+}
+
+/// Has explanations come from the bundled error index, unless something
+/// else was picked or `--live` rules it out. Returns the version of rustc
+/// the index came from if it does.
+fn use_bundle() -> Option<&'static str> {
+    if RUSTC.get().is_some() || LIVE.load(Ordering::Relaxed) {
+        return None;
+    }
+    let version = bundled::version()?;
+    RUSTC.get_or_init(|| Rustc::Bundled);
+    Some(version)
+}
+
+/// Has explanations come from a release's error index, downloading it if
+/// it isn't cached yet.
+pub fn select_release(version: &str) -> Result<(), String> {
+    let dir = releases::fetch(version)?;
+    RUSTC.get_or_init(|| Rustc::Release(version.to_string(), dir));
+    Ok(())
+}
+
+/// Whether explanations come from a stored error index rather than a
+/// rustc, either the bundled one or a release's.
+pub fn without_rustc() -> bool {
+    matches!(RUSTC.get(), Some(Rustc::Bundled | Rustc::Release(..)))
+}
+
+/// A code's entry in the stored error index explanations come from.
+pub fn stored_entry(code: &str) -> Option<IndexEntry> {
+    match RUSTC.get() {
+        Some(Rustc::Bundled) => bundled::probe(code),
+        Some(Rustc::Release(_, dir)) => releases::probe(dir, code),
+        _ => None,
+    }
+}
+
+/// A `rustc` command, ready for arguments.
+pub fn rustc() -> Command {
+    match RUSTC.get() {
+        Some(Rustc::Toolchain(toolchain)) => toolchain_rustc(toolchain),
+        Some(Rustc::Binary(path)) => Command::new(path),
+        Some(Rustc::Bundled | Rustc::Release(..)) | None => Command::new("rustc"),
+    }
+}
+
+/// A `rustdoc` command from the same toolchain as `rustc()`. A `--rustc`
+/// binary's is the one beside it, as in a local compiler build.
+pub fn rustdoc() -> Command {
+    match RUSTC.get() {
+        Some(Rustc::Toolchain(toolchain)) => {
+            let mut command = Command::new("rustup");
+            command.args(["run", toolchain, "rustdoc"]);
+            command
+        }
+        Some(Rustc::Binary(path)) => {
+            let name = format!("rustdoc{}", std::env::consts::EXE_SUFFIX);
+            Command::new(path.with_file_name(name))
+        }
+        Some(Rustc::Bundled | Rustc::Release(..)) | None => Command::new("rustdoc"),
+    }
+}
+
+/// A `cargo` command from the same toolchain as `rustc()`, as far as there
+/// is one. A `--rustc` binary comes without a cargo, so that's the default.
+pub fn cargo() -> Command {
+    match RUSTC.get() {
+        Some(Rustc::Toolchain(toolchain)) => {
+            let mut command = Command::new("rustup");
+            command.args(["run", toolchain, "cargo"]);
+            command
+        }
+        _ => Command::new(std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into())),
+    }
+}
+
+/// A `rustc` command from a particular rustup toolchain.
+pub fn toolchain_rustc(toolchain: &str) -> Command {
+    let mut command = Command::new("rustup");
+    command.args(["run", toolchain, "rustc"]);
+    command
+}
+
+/// What `--version` prints: cargo-explain's version, then the rustc that
+/// explanations come from, the binary rustup resolves it to, and which
+/// toolchain that is and why.
+pub fn version_report() -> String {
+    let mut report = format!("{} {}\n", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    match output_of(rustc().arg("--version")) {
+        Some(version) => report.push_str(&format!("{}\n", version)),
+        None => {
+            report.push_str("rustc: not found\n");
+            use_bundle();
+        }
+    }
+    match RUSTC.get() {
+        Some(Rustc::Bundled) => {
+            let version = bundled::version().unwrap_or_default();
+            report.push_str(&format!("explanations: bundled, from {}\n", version));
+            return report;
+        }
+        Some(Rustc::Release(version, dir)) => {
+            report.push_str(&format!(
+                "explanations: Rust {} (--release), cached in {}\n",
+                version,
+                dir.display()
+            ));
+            return report;
+        }
+        _ => {}
+    }
+    // A binary given with --rustc belongs to no toolchain
+    if let Some(Rustc::Binary(path)) = RUSTC.get() {
+        report.push_str(&format!("binary: {} (--rustc)\n", path.display()));
+        return report;
+    }
+    if let Some(binary) = binary() {
+        report.push_str(&format!("binary: {}\n", binary.display()));
+    }
+    // Like `stable-x86_64-unknown-linux-gnu (default)`, or naming the
+    // override or rust-toolchain.toml that chose it
+    match RUSTC.get() {
+        Some(Rustc::Toolchain(toolchain)) => match PINNED_BY.get() {
+            Some(file) => report.push_str(&format!(
+                "toolchain: {} (pinned by {})\n",
+                toolchain,
+                file.display()
+            )),
+            None => report.push_str(&format!("toolchain: {} (--toolchain)\n", toolchain)),
+        },
+        _ => {
+            if let Some(toolchain) =
+                output_of(Command::new("rustup").args(["show", "active-toolchain"]))
+            {
+                report.push_str(&format!("toolchain: {}\n", toolchain));
+            }
+        }
+    }
+    report
+}
+
+/// The sysroot of `rustc()`, where its binaries and documentation live.
+pub fn sysroot() -> Option<PathBuf> {
+    output_of(rustc().args(["--print", "sysroot"])).map(PathBuf::from)
+}
+
+/// The rustc binary itself, as opposed to rustup's proxy for it.
+fn binary() -> Option<PathBuf> {
+    let name = format!("rustc{}", std::env::consts::EXE_SUFFIX);
+    Some(sysroot()?.join("bin").join(name))
+}
+
+/// The first line of a command's output, if it succeeds.
+fn output_of(command: &mut Command) -> Option<String> {
+    let output = command.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout.lines().next().map(str::to_string)
+}
@@ -0,0 +1,93 @@
+//! `--watch`: check the project whenever its files change, explaining each
+//! error code the first time a build hits it.
+
+use notify::{RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use anstyle::Style;
+use cargo_explain::{render, Paint, RenderOptions};
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+
+use crate::{code_header, diagnostics, fetch_explanation, occurrences, styled_stdout, warn};
+
+/// How long to wait for a burst of changes, like an editor's save or a
+/// `git checkout`, to finish before checking.
+const SETTLE: Duration = Duration::from_millis(200);
+
+/// Checks `root` now and after every change to its files, until killed.
+/// A check or explanation that fails is warned about, and tried again after
+/// the next change. Explanations are rendered when `styled`, and otherwise
+/// written as rustc wrote them.
+pub fn watch(
+    root: &Path,
+    ps: &SyntaxSet,
+    ts: &ThemeSet,
+    options: &RenderOptions,
+    styled: bool,
+    locale: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let (sender, changes) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    watcher.watch(root, RecursiveMode::Recursive)?;
+    let target = diagnostics::target_dir().unwrap_or_else(|| root.join("target"));
+
+    let mut seen = HashSet::new();
+    loop {
+        let diagnostics = diagnostics::cargo_check().unwrap_or_else(|e| {
+            warn(&format!("couldn't run cargo check: {}", e));
+            Vec::new()
+        });
+        let mut stdout = styled_stdout(styled);
+        for found in diagnostics::error_codes(&diagnostics) {
+            let code = found.code;
+            if seen.contains(&code) {
+                continue;
+            }
+            match fetch_explanation(&code, locale) {
+                Ok(Some(input)) => {
+                    let location = found.first.location;
+                    let note = occurrences(found.count, &location, "this build");
+                    let snippet = found.first.snippet.as_ref().zip(location.as_ref());
+                    if styled {
+                        writeln!(stdout, "{}", code_header(&code, options))?;
+                        writeln!(stdout, "{}\n", Style::new().dimmed().paint(note))?;
+                        if let Some((snippet, location)) = snippet {
+                            writeln!(stdout, "{}", snippet.render(location, true, options.mono))?;
+                        }
+                        writeln!(stdout, "{}\n", render(&input, ps, ts, options, None))?;
+                    } else {
+                        writeln!(stdout, "{}\n{}\n", code, note)?;
+                        if let Some((snippet, location)) = snippet {
+                            writeln!(stdout, "{}", snippet.render(location, false, true))?;
+                        }
+                        writeln!(stdout, "{}", input)?;
+                    }
+                }
+                Ok(None) => warn(&format!("no explanation found for {}", code)),
+                Err(e) => {
+                    warn(&format!("couldn't explain {}: {}", code, e));
+                    continue;
+                }
+            }
+            seen.insert(code);
+        }
+        stdout.flush()?;
+        eprintln!("Watching {} for changes...", root.display());
+
+        // Wait for a change outside of cargo's own output, then for the rest
+        // of its burst
+        loop {
+            let event = changes.recv()??;
+            if event.paths.iter().any(|path| !path.starts_with(&target)) {
+                break;
+            }
+        }
+        while changes.recv_timeout(SETTLE).is_ok() {}
+    }
+}
@@ -0,0 +1,153 @@
+//! `--wrapper`: cargo-explain as `RUSTC_WRAPPER`, running rustc just as
+//! cargo asked and, when it fails, adding the explanation of each of its
+//! errors' codes to what it printed.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::time::{Duration, SystemTime};
+
+use anstream::AutoStream;
+use cargo_explain::{render, RenderOptions};
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+
+use crate::diagnostics::Diagnostic;
+use crate::{code_header, fetch_explanation, scan, warn};
+
+/// Markers older than this are from an earlier build whose cargo had the
+/// same process ID.
+const BUILD_MARKER_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// The rustc command line to run as a wrapper, if that's how cargo-explain
+/// was run: after `--wrapper`, or straight after the binary when that's a
+/// rustc, or clippy's driver for one, as cargo runs `RUSTC_WRAPPER`.
+pub fn rustc_args() -> Option<Vec<OsString>> {
+    let mut args: Vec<OsString> = env::args_os().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "--wrapper") {
+        args.remove(0);
+        return Some(args).filter(|args| !args.is_empty());
+    }
+    let rustc = Path::new(args.first()?);
+    let is_rustc = rustc
+        .file_stem()
+        .is_some_and(|stem| stem == "rustc" || stem == "clippy-driver");
+    Some(args).filter(|_| is_rustc)
+}
+
+/// Runs rustc, passing its stderr through a line at a time so cargo reads
+/// it as it would rustc's. Returns how rustc exited and the distinct error
+/// codes it reported, in order.
+pub fn run(args: &[OsString]) -> io::Result<(ExitStatus, Vec<String>)> {
+    let mut child = Command::new(&args[0])
+        .args(&args[1..])
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stderr = BufReader::new(child.stderr.take().expect("rustc's stderr is piped"));
+    let mut codes: Vec<String> = Vec::new();
+    let mut line = Vec::new();
+    while stderr.read_until(b'\n', &mut line)? > 0 {
+        io::stderr().write_all(&line)?;
+        let text = String::from_utf8_lossy(&line);
+        // cargo asks for JSON, but rustc run by hand prints errors as text
+        let found = match Diagnostic::parse(&text) {
+            Some(diagnostic) if diagnostic.level == "error" => {
+                diagnostic.code.into_iter().collect()
+            }
+            Some(_) => Vec::new(),
+            None if text.contains("error[E") => scan::codes_in_message(&text),
+            None => Vec::new(),
+        };
+        for code in found {
+            if !codes.contains(&code) {
+                codes.push(code);
+            }
+        }
+        line.clear();
+    }
+    Ok((child.wait()?, codes))
+}
+
+/// Whether the rustc command line asks for colored output, as cargo's does
+/// when it's printing to a terminal.
+pub fn wants_color(args: &[OsString]) -> bool {
+    args.iter().any(|arg| {
+        let arg = arg.to_string_lossy();
+        arg == "--color=always" || arg.contains("diagnostic-rendered-ansi")
+    })
+}
+
+/// Writes the explanation of each code to stderr, after what rustc wrote,
+/// leaving out those already explained for another crate in the same build.
+pub fn explain(
+    codes: &[String],
+    ps: &SyntaxSet,
+    ts: &ThemeSet,
+    options: &RenderOptions,
+    styled: bool,
+    locale: Option<&str>,
+) -> io::Result<()> {
+    let mut stderr = if styled {
+        AutoStream::always(io::stderr())
+    } else {
+        AutoStream::never(io::stderr())
+    };
+    for code in codes {
+        if !first_in_build(code) {
+            continue;
+        }
+        match fetch_explanation(code, locale) {
+            Ok(Some(input)) if styled => {
+                writeln!(stderr, "{}\n", code_header(code, options))?;
+                writeln!(stderr, "{}\n", render(&input, ps, ts, options, None))?;
+            }
+            Ok(Some(input)) => writeln!(stderr, "{}\n\n{}", code, input)?,
+            Ok(None) => {}
+            Err(e) => warn(&format!("couldn't explain {}: {}", code, e)),
+        }
+    }
+    Ok(())
+}
+
+/// Whether no other rustc in this build has explained `code` yet. Each
+/// crate cargo builds gets its own rustc, and so its own wrapper, so they
+/// leave a marker behind, named after the cargo running them all.
+fn first_in_build(code: &str) -> bool {
+    let marker = match build_markers() {
+        Some(markers) => markers.join(code),
+        None => return true,
+    };
+    let stale = fs::metadata(&marker)
+        .and_then(|metadata| metadata.modified())
+        .map(|modified| {
+            SystemTime::now()
+                .duration_since(modified)
+                .is_ok_and(|age| age > BUILD_MARKER_AGE)
+        });
+    if let Ok(true) = stale {
+        let _ = fs::remove_file(&marker);
+    }
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&marker)
+        .is_ok()
+}
+
+#[cfg(unix)]
+fn build_markers() -> Option<PathBuf> {
+    let cargo = std::os::unix::process::parent_id();
+    let markers = env::temp_dir()
+        .join("cargo-explain-wrapper")
+        .join(cargo.to_string());
+    fs::create_dir_all(&markers).ok()?;
+    Some(markers)
+}
+
+#[cfg(not(unix))]
+fn build_markers() -> Option<PathBuf> {
+    None
+}